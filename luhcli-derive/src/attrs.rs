@@ -0,0 +1,123 @@
+//! `#[luhcli(...)]` attributes and doc comments.
+
+use syn::punctuated::Punctuated;
+use syn::{Attribute, Expr, ExprLit, Lit, LitChar, LitStr, Meta, Token};
+
+/// Everything `#[luhcli(...)]` can say about a struct, enum variant or field.
+#[derive(Default)]
+pub(crate) struct Attrs {
+    /// `name = "..."`: command or arg name
+    pub(crate) name: Option<String>,
+    /// `about = "..."` / `help = "..."`, falling back to the doc comment
+    pub(crate) help: Option<String>,
+    /// `version = "..."` (structs)
+    pub(crate) version: Option<String>,
+    /// `short` (first letter of the name) or `short = 'x'`
+    pub(crate) short: Option<Option<char>>,
+    /// `long = "..."`
+    pub(crate) long: Option<String>,
+    /// `default = "..."`
+    pub(crate) default: Option<String>,
+    /// `env = "..."`
+    pub(crate) env: Option<String>,
+    /// `possible_values("a", "b")`
+    pub(crate) possible_values: Vec<String>,
+    /// `positional`
+    pub(crate) positional: bool,
+    /// `subcommand`
+    pub(crate) subcommand: bool,
+    /// `value_enum` (fields)
+    pub(crate) value_enum: bool,
+    /// `alias = "..."`, repeatable (`ValueEnum` variants)
+    pub(crate) aliases: Vec<String>,
+    /// `hide` (`ValueEnum` variants)
+    pub(crate) hide: bool,
+}
+
+impl Attrs {
+    pub(crate) fn parse(attrs: &[Attribute]) -> syn::Result<Self> {
+        let mut out = Self { help: doc_comment(attrs), ..Self::default() };
+
+        for attr in attrs.iter().filter(|a| a.path().is_ident("luhcli")) {
+            attr.parse_nested_meta(|meta| {
+                let key = meta.path.get_ident().map(ToString::to_string).unwrap_or_default();
+
+                match key.as_str() {
+                    "name" => out.name = Some(meta.value()?.parse::<LitStr>()?.value()),
+                    "about" | "help" => out.help = Some(meta.value()?.parse::<LitStr>()?.value()),
+                    "version" => out.version = Some(meta.value()?.parse::<LitStr>()?.value()),
+                    "long" => out.long = Some(meta.value()?.parse::<LitStr>()?.value()),
+                    "default" => out.default = Some(meta.value()?.parse::<LitStr>()?.value()),
+                    "env" => out.env = Some(meta.value()?.parse::<LitStr>()?.value()),
+                    "short" if meta.input.peek(Token![=]) => {
+                        out.short = Some(Some(meta.value()?.parse::<LitChar>()?.value()));
+                    }
+                    "short" => out.short = Some(None),
+                    "positional" => out.positional = true,
+                    "subcommand" => out.subcommand = true,
+                    "value_enum" => out.value_enum = true,
+                    "alias" => out.aliases.push(meta.value()?.parse::<LitStr>()?.value()),
+                    "hide" => out.hide = true,
+                    "possible_values" => {
+                        let content;
+                        syn::parenthesized!(content in meta.input);
+                        let values = Punctuated::<LitStr, Token![,]>::parse_terminated(&content)?;
+                        out.possible_values = values.iter().map(LitStr::value).collect();
+                    }
+                    _ => return Err(meta.error("unknown luhcli attribute")),
+                }
+
+                Ok(())
+            })?;
+        }
+
+        Ok(out)
+    }
+}
+
+/// The first paragraph of the doc comment, on one line.
+fn doc_comment(attrs: &[Attribute]) -> Option<String> {
+    let lines = attrs.iter()
+        .filter_map(|attr| match &attr.meta {
+            Meta::NameValue(nv) if nv.path.is_ident("doc") => match &nv.value {
+                Expr::Lit(ExprLit { lit: Lit::Str(s), .. }) => Some(s.value()),
+                _ => None,
+            },
+            _ => None,
+        })
+        .map(|line| line.trim().to_string())
+        .skip_while(String::is_empty)
+        .take_while(|line| !line.is_empty())
+        .collect::<Vec<_>>();
+
+    (!lines.is_empty()).then(|| lines.join(" "))
+}
+
+/// `SomeName` -> `some-name`, `some_name` -> `some-name`, `JSONValue` -> `json-value`.
+pub(crate) fn kebab_case(ident: &str) -> String {
+    let ident = ident.strip_prefix("r#").unwrap_or(ident);
+    let chars: Vec<char> = ident.chars().collect();
+    let mut out = String::new();
+
+    for (i, &c) in chars.iter().enumerate() {
+        if c.is_uppercase() {
+            // a word starts after a lowercase letter or digit, or at the last
+            // capital of a run followed by lowercase (`HTTPServer` -> `http-server`)
+            let prev = i.checked_sub(1).map(|p| chars[p]);
+            let next = chars.get(i + 1);
+            let starts_word = prev.is_some_and(|p| {
+                !p.is_uppercase() || next.is_some_and(|n| n.is_lowercase())
+            });
+            if starts_word && !out.ends_with('-') {
+                out.push('-');
+            }
+            out.extend(c.to_lowercase());
+        } else if c == '_' {
+            out.push('-');
+        } else {
+            out.push(c);
+        }
+    }
+
+    out
+}
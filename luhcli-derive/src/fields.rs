@@ -0,0 +1,192 @@
+//! Named fields to `Arg`s, shared by structs and struct-like enum variants.
+
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{Error, Fields, GenericArgument, PathArguments, Type};
+
+use crate::attrs::{Attrs, kebab_case};
+
+/// Generated code for a set of fields.
+pub(crate) struct Expanded {
+    /// Statements adding the args (and subcommands) to a mutable `cmd`
+    pub(crate) build: TokenStream,
+    /// `field: value,` initialisers reading from `args: &ParsedArgs`
+    pub(crate) init: TokenStream,
+    /// Whether a subcommand has to be given, so the command can't run on its own
+    pub(crate) needs_subcommand: bool,
+}
+
+/// How a field is read from the command line.
+enum Kind<'a> {
+    Flag,
+    Option { ty: &'a Type, required: bool },
+    Positional { ty: &'a Type, required: bool, index: usize },
+    Variadic { ty: &'a Type },
+    Subcommand { ty: &'a Type, required: bool },
+}
+
+pub(crate) fn expand(fields: &Fields) -> syn::Result<Expanded> {
+    let Fields::Named(fields) = fields else {
+        return Err(Error::new_spanned(fields, "luhcli derives need named fields"));
+    };
+
+    let mut build = TokenStream::new();
+    let mut init = TokenStream::new();
+    let mut needs_subcommand = false;
+    let mut positionals = 0;
+
+    for field in &fields.named {
+        let ident = field.ident.as_ref().expect("named field");
+        let attrs = Attrs::parse(&field.attrs)?;
+        let name = attrs.name.clone().unwrap_or_else(|| kebab_case(&ident.to_string()));
+
+        let optional = wrapped(&field.ty, "Option");
+        let kind = if attrs.subcommand {
+            Kind::Subcommand { ty: optional.unwrap_or(&field.ty), required: optional.is_none() }
+        } else if is_bool(&field.ty) {
+            Kind::Flag
+        } else if let Some(ty) = wrapped(&field.ty, "Vec") {
+            Kind::Variadic { ty }
+        } else if attrs.positional {
+            positionals += 1;
+            Kind::Positional {
+                ty: optional.unwrap_or(&field.ty),
+                required: optional.is_none() && attrs.default.is_none(),
+                index: positionals - 1,
+            }
+        } else {
+            Kind::Option {
+                ty: optional.unwrap_or(&field.ty),
+                required: optional.is_none() && attrs.default.is_none(),
+            }
+        };
+
+        // paths and OS strings keep their exact bytes
+        let raw = match &kind {
+            Kind::Option { ty, .. } | Kind::Positional { ty, .. } | Kind::Variadic { ty } => is_os_string(ty),
+            Kind::Flag | Kind::Subcommand { .. } => false,
+        };
+        if raw && attrs.value_enum {
+            return Err(Error::new_spanned(ident, "value_enum fields can't be paths or OS strings"));
+        }
+        let (required, optional_value, many) = if raw {
+            (quote!(required_os), quote!(optional_os), quote!(many_os))
+        } else if attrs.value_enum {
+            (quote!(required_enum), quote!(optional_enum), quote!(many_enum))
+        } else {
+            (quote!(required), quote!(optional), quote!(many))
+        };
+
+        let support = quote!(::luhcli::derive_support);
+        let value = match &kind {
+            Kind::Flag => quote!(args.flag(#name)),
+            Kind::Option { ty, required: true } | Kind::Positional { ty, required: true, .. } => {
+                quote!(#support::#required::<#ty>(args, #name)?)
+            }
+            Kind::Option { ty, required: false } | Kind::Positional { ty, required: false, .. } => {
+                if optional.is_some() {
+                    quote!(#support::#optional_value::<#ty>(args, #name)?)
+                } else {
+                    // not optional but has a default, which is always there
+                    quote!(#support::#required::<#ty>(args, #name)?)
+                }
+            }
+            Kind::Variadic { ty } => quote!(#support::#many::<#ty>(args, #name)?),
+            Kind::Subcommand { ty, required: true } => quote!(#support::subcommand::<#ty>(args)?),
+            Kind::Subcommand { ty, required: false } => quote!(#support::optional_subcommand::<#ty>(args)?),
+        };
+        init.extend(quote!(#ident: #value,));
+
+        if let Kind::Subcommand { ty, required } = kind {
+            needs_subcommand |= required;
+            build.extend(quote! {
+                for sub in <#ty as ::luhcli::LuhSubcommand>::subcommands() {
+                    cmd = cmd.subcommand(sub);
+                }
+            });
+            continue;
+        }
+
+        let mut arg = match kind {
+            Kind::Flag => quote!(::luhcli::Arg::new(#name)),
+            Kind::Option { required, .. } => quote!(::luhcli::Arg::new(#name).takes_value().required(#required)),
+            Kind::Positional { required, index, .. } => quote!(::luhcli::Arg::positional(#name, #index).required(#required)),
+            Kind::Variadic { .. } => quote!(::luhcli::Arg::variadic(#name)),
+            Kind::Subcommand { .. } => unreachable!(),
+        };
+
+        let takes_flags = matches!(kind, Kind::Flag | Kind::Option { .. });
+        if !takes_flags && (attrs.short.is_some() || attrs.long.is_some()) {
+            return Err(Error::new_spanned(ident, "positional and variadic fields can't have short or long flags"));
+        }
+
+        if raw {
+            arg.extend(quote!(.raw()));
+        }
+        if let Some(short) = attrs.short {
+            let short = short.or_else(|| name.chars().next()).expect("non-empty name");
+            arg.extend(quote!(.short(#short)));
+        }
+        if let Some(long) = &attrs.long {
+            arg.extend(quote!(.long(#long)));
+        }
+        if let Some(help) = &attrs.help {
+            arg.extend(quote!(.help(#help)));
+        }
+        if let Some(default) = &attrs.default {
+            arg.extend(quote!(.default_value(#default)));
+        }
+        if let Some(env) = &attrs.env {
+            arg.extend(quote!(.env(#env)));
+        }
+        if attrs.value_enum {
+            let ty = match &kind {
+                Kind::Option { ty, .. } | Kind::Positional { ty, .. } | Kind::Variadic { ty } => ty,
+                Kind::Flag | Kind::Subcommand { .. } => {
+                    return Err(Error::new_spanned(ident, "value_enum needs a field taking a value"));
+                }
+            };
+            arg.extend(quote!(.value_enum::<#ty>()));
+        }
+        if !attrs.possible_values.is_empty() {
+            let values = &attrs.possible_values;
+            arg.extend(quote!(.possible_values([#(#values),*])));
+        }
+
+        build.extend(quote!(cmd = cmd.arg(#arg);));
+    }
+
+    Ok(Expanded { build, init, needs_subcommand })
+}
+
+/// `T` when `ty` is `wrapper<T>` (e.g. `Option<T>`).
+fn wrapped<'a>(ty: &'a Type, wrapper: &str) -> Option<&'a Type> {
+    let Type::Path(path) = ty else {
+        return None;
+    };
+
+    let segment = path.path.segments.last()?;
+    if segment.ident != wrapper {
+        return None;
+    }
+
+    match &segment.arguments {
+        PathArguments::AngleBracketed(args) if args.args.len() == 1 => match args.args.first()? {
+            GenericArgument::Type(inner) => Some(inner),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn is_bool(ty: &Type) -> bool {
+    matches!(ty, Type::Path(path) if path.path.is_ident("bool"))
+}
+
+/// `PathBuf` or `OsString`, read without going through UTF-8.
+fn is_os_string(ty: &Type) -> bool {
+    let Type::Path(path) = ty else {
+        return false;
+    };
+    path.path.segments.last().is_some_and(|s| s.ident == "PathBuf" || s.ident == "OsString")
+}
@@ -0,0 +1,47 @@
+//! Derive macros for [luhcli](https://docs.rs/luhcli), re-exported by its `derive` feature.
+//!
+//! - `#[derive(LuhParser)]` on a struct with named fields implements `luhcli::LuhParser`
+//! - `#[derive(LuhSubcommand)]` on an enum implements `luhcli::LuhSubcommand`
+//! - `#[derive(ValueEnum)]` on an enum of unit variants implements `luhcli::ValueEnum`
+//!
+//! All read `#[luhcli(...)]` attributes and use doc comments as help text,
+//! see the `LuhParser` and `ValueEnum` traits in luhcli for the full list.
+
+use proc_macro::TokenStream;
+use syn::{DeriveInput, parse_macro_input};
+
+mod attrs;
+mod fields;
+mod parser;
+mod subcommand;
+mod value_enum;
+
+#[cfg(test)]
+mod tests;
+
+/// Implement `luhcli::LuhParser` for a struct, one `Arg` per field.
+#[proc_macro_derive(LuhParser, attributes(luhcli))]
+pub fn derive_parser(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    parser::expand(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Implement `luhcli::LuhSubcommand` for an enum, one `Command` per variant.
+#[proc_macro_derive(LuhSubcommand, attributes(luhcli))]
+pub fn derive_subcommand(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    subcommand::expand(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Implement `luhcli::ValueEnum` for an enum, one possible value per variant.
+#[proc_macro_derive(ValueEnum, attributes(luhcli))]
+pub fn derive_value_enum(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    value_enum::expand(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
@@ -0,0 +1,50 @@
+//! `#[derive(LuhParser)]`
+
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{Data, DeriveInput, Error};
+
+use crate::attrs::Attrs;
+use crate::fields;
+
+pub(crate) fn expand(input: DeriveInput) -> syn::Result<TokenStream> {
+    let Data::Struct(data) = &input.data else {
+        return Err(Error::new_spanned(&input.ident, "LuhParser can only be derived for structs"));
+    };
+
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let attrs = Attrs::parse(&input.attrs)?;
+    let fields::Expanded { build, init, needs_subcommand } = fields::expand(&data.fields)?;
+
+    let name = match &attrs.name {
+        Some(name) => quote!(#name),
+        None => quote!(env!("CARGO_PKG_NAME")),
+    };
+    let about = attrs.help.iter();
+    let runnable = (!needs_subcommand).then(|| quote!(cmd = cmd.handler(|_| Ok(()));));
+
+    let app = attrs.version.map(|version| quote! {
+        fn app() -> ::luhcli::CliApp {
+            ::luhcli::CliApp::from_command(Self::command()).version(#version)
+        }
+    });
+
+    Ok(quote! {
+        impl #impl_generics ::luhcli::LuhParser for #ident #ty_generics #where_clause {
+            fn command() -> ::luhcli::Command {
+                let mut cmd = ::luhcli::Command::new(#name) #(.about(#about))*;
+                #build
+                #runnable
+                cmd
+            }
+
+            fn from_args(args: &::luhcli::ParsedArgs) -> ::luhcli::derive_support::LuhTwin<Self> {
+                let _ = args;
+                Ok(Self { #init })
+            }
+
+            #app
+        }
+    })
+}
@@ -0,0 +1,78 @@
+//! `#[derive(LuhSubcommand)]`
+
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{Data, DeriveInput, Error, Fields};
+
+use crate::attrs::{Attrs, kebab_case};
+use crate::fields;
+
+pub(crate) fn expand(input: DeriveInput) -> syn::Result<TokenStream> {
+    let Data::Enum(data) = &input.data else {
+        return Err(Error::new_spanned(&input.ident, "LuhSubcommand can only be derived for enums"));
+    };
+
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let mut commands = Vec::new();
+    let mut arms = Vec::new();
+
+    for variant in &data.variants {
+        let attrs = Attrs::parse(&variant.attrs)?;
+        let variant_ident = &variant.ident;
+        let name = attrs.name.clone().unwrap_or_else(|| kebab_case(&variant_ident.to_string()));
+        let about = attrs.help.iter();
+
+        match &variant.fields {
+            Fields::Unit => {
+                commands.push(quote! {
+                    ::luhcli::Command::new(#name) #(.about(#about))* .handler(|_| Ok(()))
+                });
+                arms.push(quote!(#name => Ok(Self::#variant_ident),));
+            }
+            Fields::Named(_) => {
+                let fields::Expanded { build, init, needs_subcommand } = fields::expand(&variant.fields)?;
+                let runnable = (!needs_subcommand).then(|| quote!(cmd = cmd.handler(|_| Ok(()));));
+
+                commands.push(quote! {{
+                    let mut cmd = ::luhcli::Command::new(#name) #(.about(#about))*;
+                    #build
+                    #runnable
+                    cmd
+                }});
+                arms.push(quote!(#name => Ok(Self::#variant_ident { #init }),));
+            }
+            Fields::Unnamed(unnamed) if unnamed.unnamed.len() == 1 => {
+                let ty = &unnamed.unnamed[0].ty;
+
+                commands.push(quote! {
+                    ::luhcli::derive_support::rename(<#ty as ::luhcli::LuhParser>::command(), #name)
+                        #(.about(#about))*
+                });
+                arms.push(quote! {
+                    #name => Ok(Self::#variant_ident(<#ty as ::luhcli::LuhParser>::from_args(args)?)),
+                });
+            }
+            Fields::Unnamed(_) => {
+                return Err(Error::new_spanned(variant, "tuple variants must wrap exactly one LuhParser type"));
+            }
+        }
+    }
+
+    Ok(quote! {
+        impl #impl_generics ::luhcli::LuhSubcommand for #ident #ty_generics #where_clause {
+            fn subcommands() -> Vec<::luhcli::Command> {
+                vec![#(#commands),*]
+            }
+
+            fn from_subcommand(name: &str, args: &::luhcli::ParsedArgs) -> ::luhcli::derive_support::LuhTwin<Self> {
+                let _ = args;
+                match name {
+                    #(#arms)*
+                    other => ::luhcli::derive_support::unknown(other),
+                }
+            }
+        }
+    })
+}
@@ -0,0 +1,98 @@
+use syn::{DeriveInput, parse_quote};
+
+use crate::attrs::kebab_case;
+use crate::{parser, subcommand, value_enum};
+
+fn parser_error(input: DeriveInput) -> String {
+    parser::expand(input).unwrap_err().to_string()
+}
+
+fn subcommand_error(input: DeriveInput) -> String {
+    subcommand::expand(input).unwrap_err().to_string()
+}
+
+fn value_enum_error(input: DeriveInput) -> String {
+    value_enum::expand(input).unwrap_err().to_string()
+}
+
+#[test]
+fn names() {
+    assert_eq!(kebab_case("dry_run"), "dry-run");
+    assert_eq!(kebab_case("ListAll"), "list-all");
+    assert_eq!(kebab_case("r#type"), "type");
+    assert_eq!(kebab_case("HTTPServer"), "http-server");
+    assert_eq!(kebab_case("JSON"), "json");
+    assert_eq!(kebab_case("ParseJSON"), "parse-json");
+    assert_eq!(kebab_case("IOError"), "io-error");
+    assert_eq!(kebab_case("Http2Server"), "http2-server");
+    assert_eq!(kebab_case("use_TLS"), "use-tls");
+}
+
+#[test]
+fn wrong_items() {
+    assert_eq!(parser_error(parse_quote!(enum Cli { A })), "LuhParser can only be derived for structs");
+    assert_eq!(subcommand_error(parse_quote!(struct Action;)), "LuhSubcommand can only be derived for enums");
+    assert_eq!(value_enum_error(parse_quote!(struct Mode;)), "ValueEnum can only be derived for enums");
+    assert_eq!(parser_error(parse_quote!(struct Cli(String);)), "luhcli derives need named fields");
+}
+
+#[test]
+fn bad_attributes() {
+    assert_eq!(
+        parser_error(parse_quote!(struct Cli { #[luhcli(shrot)] verbose: bool })),
+        "unknown luhcli attribute"
+    );
+    assert_eq!(
+        parser_error(parse_quote!(struct Cli { #[luhcli(long = 3)] jobs: u32 })),
+        "expected string literal"
+    );
+    assert_eq!(
+        parser_error(parse_quote!(struct Cli { #[luhcli(positional, short)] file: String })),
+        "positional and variadic fields can't have short or long flags"
+    );
+    assert_eq!(
+        parser_error(parse_quote!(struct Cli { #[luhcli(long = "all")] files: Vec<String> })),
+        "positional and variadic fields can't have short or long flags"
+    );
+}
+
+#[test]
+fn bad_value_enum_fields() {
+    assert_eq!(
+        parser_error(parse_quote!(struct Cli { #[luhcli(value_enum)] out: std::path::PathBuf })),
+        "value_enum fields can't be paths or OS strings"
+    );
+    assert_eq!(
+        parser_error(parse_quote!(struct Cli { #[luhcli(value_enum)] fast: bool })),
+        "value_enum needs a field taking a value"
+    );
+}
+
+#[test]
+fn bad_variants() {
+    assert_eq!(
+        subcommand_error(parse_quote!(enum Action { Copy(From, To) })),
+        "tuple variants must wrap exactly one LuhParser type"
+    );
+    assert_eq!(
+        subcommand_error(parse_quote!(enum Action { Add { #[luhcli(bogus)] name: String } })),
+        "unknown luhcli attribute"
+    );
+    assert_eq!(value_enum_error(parse_quote!(enum Mode { Fast(u32) })), "ValueEnum variants can't have fields");
+}
+
+#[test]
+fn valid_input_expands() {
+    assert!(parser::expand(parse_quote! {
+        /// A tool
+        struct Cli {
+            #[luhcli(short = 'j', default = "4")]
+            jobs: u32,
+            #[luhcli(positional)]
+            file: Option<std::path::PathBuf>,
+            rest: Vec<String>,
+        }
+    }).is_ok());
+    assert!(subcommand::expand(parse_quote!(enum Action { Copy(Copy), #[luhcli(name = "ls")] List })).is_ok());
+    assert!(value_enum::expand(parse_quote!(enum Mode { Fast, #[luhcli(alias = "s", hide)] Slow })).is_ok());
+}
@@ -0,0 +1,52 @@
+//! `#[derive(ValueEnum)]`
+
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{Data, DeriveInput, Error, Fields};
+
+use crate::attrs::{Attrs, kebab_case};
+
+pub(crate) fn expand(input: DeriveInput) -> syn::Result<TokenStream> {
+    let Data::Enum(data) = &input.data else {
+        return Err(Error::new_spanned(&input.ident, "ValueEnum can only be derived for enums"));
+    };
+
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let mut variants = Vec::new();
+    let mut arms = Vec::new();
+
+    for variant in &data.variants {
+        if !matches!(variant.fields, Fields::Unit) {
+            return Err(Error::new_spanned(variant, "ValueEnum variants can't have fields"));
+        }
+
+        let attrs = Attrs::parse(&variant.attrs)?;
+        let variant_ident = &variant.ident;
+        let name = attrs.name.clone().unwrap_or_else(|| kebab_case(&variant_ident.to_string()));
+        let help = attrs.help.iter();
+        let aliases = &attrs.aliases;
+        let hide = attrs.hide;
+
+        variants.push(quote!(Self::#variant_ident));
+        arms.push(quote! {
+            Self::#variant_ident => ::luhcli::PossibleValue::new(#name)
+                #(.help(#help))* #(.alias(#aliases))* .hide(#hide),
+        });
+    }
+
+    Ok(quote! {
+        impl #impl_generics ::luhcli::ValueEnum for #ident #ty_generics #where_clause {
+            fn value_variants() -> &'static [Self] {
+                &[#(#variants),*]
+            }
+
+            fn to_possible_value(&self) -> ::luhcli::PossibleValue {
+                match self {
+                    #(#arms)*
+                }
+            }
+        }
+    })
+}
@@ -0,0 +1,167 @@
+//! Configuration file layer, see `CliApp::config_file`.
+//!
+//! Loading and parsing files needs the `config` feature, without it the layer
+//! is always empty and `Command::parse` only sees the environment and defaults.
+
+use std::collections::HashMap;
+
+#[cfg(feature = "config")]
+use std::path::{Path, PathBuf};
+#[cfg(feature = "config")]
+use luhtwin::{LuhTwin, Wrap, at};
+#[cfg(feature = "config")]
+use crate::{Command, collect_arg_names};
+
+/// Values for one command read from a configuration file, keyed by arg name.
+#[cfg_attr(not(feature = "config"), allow(dead_code))]
+pub(crate) struct ConfigSection {
+    /// Where the values came from, for error messages (e.g. `config file app.toml [remote]`)
+    pub(crate) origin: String,
+    /// Values of each arg, flags as `true`/`false` and arrays as several values
+    pub(crate) values: HashMap<String, Vec<String>>,
+}
+
+/// A loaded configuration file, one section per command path.
+#[derive(Default)]
+pub(crate) struct ConfigLayer {
+    sections: HashMap<Vec<String>, ConfigSection>,
+}
+
+impl ConfigLayer {
+    /// The values for a command, `command_path` starting with the app name.
+    pub(crate) fn section(&self, command_path: &[String]) -> Option<&ConfigSection> {
+        self.sections.get(command_path.get(1..).unwrap_or_default())
+    }
+}
+
+#[cfg(feature = "config")]
+impl ConfigLayer {
+    /// Load the first of `candidates` that exists, checking its keys against the command tree.
+    pub(crate) fn load_first(candidates: &[PathBuf], root: &Command) -> LuhTwin<Self> {
+        let mut layer = Self::default();
+
+        let Some(path) = candidates.iter().find(|p| p.is_file()) else {
+            return Ok(layer);
+        };
+
+        let table = read_file(path)?;
+        layer.collect(root, Vec::new(), table, &path.display().to_string())?;
+        Ok(layer)
+    }
+
+    fn collect(&mut self, cmd: &Command, path: Vec<String>, table: Vec<(String, Node)>, file: &str) -> LuhTwin<()> {
+        let origin = if path.is_empty() {
+            format!("config file {}", file)
+        } else {
+            format!("config file {} [{}]", file, path.join("."))
+        };
+
+        let mut known = Vec::new();
+        collect_arg_names(&cmd.args, &mut known);
+        let mut values = HashMap::new();
+
+        for (key, node) in table {
+            match node {
+                Node::Table(table) => {
+                    let Some(sub) = cmd.subcommands.iter().find(|s| s.name == key) else {
+                        return Err(at!("unknown section '{}' in {}: no such command", key, origin).into());
+                    };
+
+                    let mut sub_path = path.clone();
+                    sub_path.push(key);
+                    self.collect(sub, sub_path, table, file)?;
+                }
+                Node::Values(vals) => {
+                    if !known.contains(&key) {
+                        return Err(at!("unknown key '{}' in {}: no such argument", key, origin).into());
+                    }
+                    values.insert(key, vals);
+                }
+            }
+        }
+
+        self.sections.insert(path, ConfigSection { origin, values });
+        Ok(())
+    }
+}
+
+/// A config file entry: a table (section) or the value(s) of an arg.
+#[cfg(feature = "config")]
+enum Node {
+    Table(Vec<(String, Node)>),
+    Values(Vec<String>),
+}
+
+#[cfg(feature = "config")]
+fn read_file(path: &Path) -> LuhTwin<Vec<(String, Node)>> {
+    let text = std::fs::read_to_string(path)
+        .wrap(|| format!("failed to read config file {}", path.display()))?;
+
+    let root = match path.extension().and_then(|e| e.to_str()) {
+        Some("toml") => {
+            let value: toml::Value = text.parse()
+                .wrap(|| format!("invalid TOML in config file {}", path.display()))?;
+            from_toml(value)
+        }
+        Some("json") => {
+            let value: serde_json::Value = serde_json::from_str(&text)
+                .wrap(|| format!("invalid JSON in config file {}", path.display()))?;
+            from_json(value)
+        }
+        _ => return Err(at!("unsupported config file {} (expected .toml or .json)", path.display()).into()),
+    };
+
+    match root {
+        Some(Node::Table(table)) => Ok(table),
+        Some(Node::Values(_)) => Err(at!("config file {} must contain a table at the top level", path.display()).into()),
+        None => Err(at!(
+            "unsupported value in config file {} (nulls, nested arrays and arrays of tables are not allowed)",
+            path.display()
+        ).into()),
+    }
+}
+
+/// Convert a TOML value, `None` for arrays holding tables or other arrays.
+#[cfg(feature = "config")]
+fn from_toml(value: toml::Value) -> Option<Node> {
+    use toml::Value;
+
+    let scalar = |value: Value| match value {
+        Value::String(s) => Some(s),
+        Value::Array(_) | Value::Table(_) => None,
+        other => Some(other.to_string()),
+    };
+
+    Some(match value {
+        Value::Table(table) => Node::Table(
+            table.into_iter()
+                .map(|(k, v)| from_toml(v).map(|node| (k, node)))
+                .collect::<Option<_>>()?,
+        ),
+        Value::Array(items) => Node::Values(items.into_iter().map(scalar).collect::<Option<_>>()?),
+        other => Node::Values(vec![scalar(other)?]),
+    })
+}
+
+/// Convert a JSON value, `None` for nulls and arrays holding objects or other arrays.
+#[cfg(feature = "config")]
+fn from_json(value: serde_json::Value) -> Option<Node> {
+    use serde_json::Value;
+
+    let scalar = |value: Value| match value {
+        Value::String(s) => Some(s),
+        Value::Bool(b) => Some(b.to_string()),
+        Value::Number(n) => Some(n.to_string()),
+        _ => None,
+    };
+
+    Some(match value {
+        Value::Object(map) => Node::Table(
+            map.into_iter()
+                .map(|(k, v)| from_json(v).map(|node| (k, node)))
+                .collect::<Option<_>>()?,
+        ),
+        Value::Array(items) => Node::Values(items.into_iter().map(scalar).collect::<Option<_>>()?),
+        other => Node::Values(vec![scalar(other)?]),
+    })
+}
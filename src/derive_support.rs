@@ -0,0 +1,123 @@
+//! Helpers for the code generated by `#[derive(LuhParser)]` and
+//! `#[derive(LuhSubcommand)]`. Not public API.
+
+use std::ffi::OsStr;
+use std::fmt::Display;
+use std::str::FromStr;
+pub use luhtwin::LuhTwin;
+use luhtwin::at;
+use crate::{Command, LuhSubcommand, ParsedArgs, ValueEnum};
+
+/// The value of a required arg (or one with a default), converted with `FromStr`.
+pub fn required<T>(args: &ParsedArgs, name: &str) -> LuhTwin<T>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    match args.get(name) {
+        Some(value) => convert(name, value),
+        None => Err(at!("required argument '{}' not provided", name).into()),
+    }
+}
+
+/// The value of an optional arg, converted with `FromStr`.
+pub fn optional<T>(args: &ParsedArgs, name: &str) -> LuhTwin<Option<T>>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    args.get(name).map(|value| convert(name, value)).transpose()
+}
+
+/// The values of the variadic arg, converted with `FromStr`.
+pub fn many<T>(args: &ParsedArgs, name: &str) -> LuhTwin<Vec<T>>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    args.variadic().iter().map(|value| convert(name, value)).collect()
+}
+
+/// The exact value of a required raw arg (`PathBuf`, `OsString`).
+pub fn required_os<T>(args: &ParsedArgs, name: &str) -> LuhTwin<T>
+where
+    T: for<'a> From<&'a OsStr>,
+{
+    match args.get_os(name) {
+        Some(value) => Ok(T::from(value)),
+        None => Err(at!("required argument '{}' not provided", name).into()),
+    }
+}
+
+/// The exact value of an optional raw arg.
+pub fn optional_os<T>(args: &ParsedArgs, name: &str) -> LuhTwin<Option<T>>
+where
+    T: for<'a> From<&'a OsStr>,
+{
+    Ok(args.get_os(name).map(T::from))
+}
+
+/// The exact values of a raw variadic arg.
+pub fn many_os<T>(args: &ParsedArgs, _name: &str) -> LuhTwin<Vec<T>>
+where
+    T: for<'a> From<&'a OsStr>,
+{
+    Ok(args.variadic_os().into_iter().map(T::from).collect())
+}
+
+/// The variant named by a required `value_enum` arg.
+pub fn required_enum<T: ValueEnum>(args: &ParsedArgs, name: &str) -> LuhTwin<T> {
+    match args.get(name) {
+        Some(value) => variant(name, value),
+        None => Err(at!("required argument '{}' not provided", name).into()),
+    }
+}
+
+/// The variant named by an optional `value_enum` arg.
+pub fn optional_enum<T: ValueEnum>(args: &ParsedArgs, name: &str) -> LuhTwin<Option<T>> {
+    args.get(name).map(|value| variant(name, value)).transpose()
+}
+
+/// The variants named by a variadic `value_enum` arg.
+pub fn many_enum<T: ValueEnum>(args: &ParsedArgs, name: &str) -> LuhTwin<Vec<T>> {
+    args.variadic().iter().map(|value| variant(name, value)).collect()
+}
+
+/// The subcommand that has to be given.
+pub fn subcommand<T: LuhSubcommand>(args: &ParsedArgs) -> LuhTwin<T> {
+    match optional_subcommand(args)? {
+        Some(sub) => Ok(sub),
+        None => Err(at!("a subcommand is required").into()),
+    }
+}
+
+/// The subcommand, if one was given.
+pub fn optional_subcommand<T: LuhSubcommand>(args: &ParsedArgs) -> LuhTwin<Option<T>> {
+    args.subcommand()
+        .map(|(name, sub)| T::from_subcommand(name, sub))
+        .transpose()
+}
+
+/// A command under another name, for enum variants wrapping a `LuhParser` struct.
+pub fn rename(cmd: Command, name: &str) -> Command {
+    Command { name: name.to_string(), ..cmd }
+}
+
+/// The error for a subcommand the enum doesn't know.
+pub fn unknown<T>(name: &str) -> LuhTwin<T> {
+    Err(at!("unknown command: {}", name).into())
+}
+
+fn convert<T>(name: &str, value: &str) -> LuhTwin<T>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    value.parse()
+        .map_err(|e| at!("invalid value '{}' for '{}': {}", value, name, e).into())
+}
+
+fn variant<T: ValueEnum>(name: &str, value: &str) -> LuhTwin<T> {
+    T::from_value(value)
+        .ok_or_else(|| at!("invalid value '{}' for '{}'", value, name).into())
+}
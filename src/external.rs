@@ -0,0 +1,59 @@
+//! External subcommands (`<prefix>-<name>` executables on `PATH`), see
+//! `CliApp::external_subcommands`.
+
+use std::path::{Path, PathBuf};
+use std::env::consts::EXE_SUFFIX;
+
+/// Find the executable for subcommand `name` on `PATH`.
+pub(crate) fn find(prefix: &str, name: &str) -> Option<PathBuf> {
+    if !is_valid(name) {
+        return None;
+    }
+
+    let file = format!("{}-{}{}", prefix, name, EXE_SUFFIX);
+    std::env::split_paths(&std::env::var_os("PATH")?)
+        .map(|dir| dir.join(&file))
+        .find(|path| is_executable(path))
+}
+
+/// Names of all external subcommands on `PATH`, sorted and without duplicates.
+pub(crate) fn list(prefix: &str) -> Vec<String> {
+    let Some(paths) = std::env::var_os("PATH") else {
+        return Vec::new();
+    };
+
+    let start = format!("{}-", prefix);
+    let mut names: Vec<String> = std::env::split_paths(&paths)
+        .filter_map(|dir| std::fs::read_dir(dir).ok())
+        .flatten()
+        .flatten()
+        .filter(|entry| is_executable(&entry.path()))
+        .filter_map(|entry| {
+            let file = entry.file_name().into_string().ok()?;
+            let name = file.strip_prefix(&start)?.strip_suffix(EXE_SUFFIX)?;
+            is_valid(name).then(|| name.to_string())
+        })
+        .collect();
+
+    names.sort();
+    names.dedup();
+    names
+}
+
+/// Whether `name` can name an external subcommand: not empty, not starting
+/// with `.` and without path separators, so it can't point outside `PATH`.
+fn is_valid(name: &str) -> bool {
+    !name.is_empty() && !name.starts_with('.') && !name.chars().any(std::path::is_separator)
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+
+    path.metadata().is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
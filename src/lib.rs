@@ -18,6 +18,15 @@
 //! - Variadic arguments (capture remaining arguments)
 //! - Conditional sub-arguments
 //!
+//! ## Optional Features
+//!
+//! - `async` – `Command::async_handler`, `Command::async_context_handler` and `CliApp::run_async` for handlers returning futures
+//! - `config` – `CliApp::config_file` to read argument values from TOML or JSON files
+//! - `prompt` – `Arg::prompt` and `Arg::secret` to ask for missing args on a terminal
+//! - `repl` – `CliApp::shell` for an interactive shell with history and tab completion
+//! - `derive` – `#[derive(LuhParser)]`, `#[derive(LuhSubcommand)]` and `#[derive(ValueEnum)]` to declare a CLI as structs and enums
+//! - `regex` – `Arg::matches` to check argument values against a regular expression
+//!
 //! ## Getting Started
 //!
 //! Add `luhcli` to your `Cargo.toml`
@@ -92,7 +101,7 @@
 //! ### All together
 //!
 //! ```ignore
-//! fn handle_config_command(args: &ParsedArgs) -> LuhTwin<()> {
+//! fn handle_config_command(args: &ParsedArgs) -> LuhTwin<i32> {
 //!     match args.get("action").map(|s| s.as_str()) {
 //!         Some("list") => {
 //!             // do something
@@ -100,14 +109,13 @@
 //! 
 //!         // more here
 //! 
-//!         _ => {
-//!             exit(3);
-//!         }
+//!         // unknown action: exit code 3
+//!         _ => return Ok(3),
 //!     }
-//!     Ok(())
+//!     Ok(0)
 //! }
 //! 
-//! fn handle_app_command(args: &ParsedArgs) -> LuhTwin<()> {
+//! fn handle_app_command(args: &ParsedArgs) -> LuhTwin<i32> {
 //!     match args.get("action").map(|s| s.as_str()) {
 //!         Some("start") => {
 //!             // do something
@@ -115,11 +123,10 @@
 //! 
 //!         // more here
 //! 
-//!         _ => {
-//!             exit(3);
-//!         }
+//!         // unknown action: exit code 3
+//!         _ => return Ok(3),
 //!     }
-//!     Ok(())
+//!     Ok(0)
 //! }
 //! 
 //! fn main() -> LuhTwin<() {
@@ -166,9 +173,72 @@
 #[cfg(test)]
 mod tests;
 
+mod config;
+mod response;
+mod external;
+mod os;
+mod paths;
+#[cfg(feature = "prompt")]
+mod prompt;
+#[cfg(feature = "repl")]
+mod repl;
+#[doc(hidden)]
+pub mod derive_support;
+
+#[cfg(feature = "derive")]
+pub use luhcli_derive::{LuhParser, LuhSubcommand, ValueEnum};
+
+use std::any::{Any, TypeId, type_name};
 use std::collections::HashMap;
-use luhlog::error;
-use luhtwin::{LuhTwin, at};
+use std::ffi::{OsStr, OsString};
+use std::fmt::Display;
+use std::ops::{Bound, RangeBounds};
+use std::path::Path;
+use std::rc::Rc;
+use std::str::FromStr;
+use luhtwin::{LuhTwin, Wrap, at};
+use config::{ConfigLayer, ConfigSection};
+use os::RawArgs;
+
+/// Exit code for a successful run, also used after printing help or the version.
+pub const EXIT_SUCCESS: i32 = 0;
+/// Exit code used by `CliApp::run_and_exit` when a handler returns an error.
+pub const EXIT_FAILURE: i32 = 1;
+/// Exit code used by `CliApp::run_and_exit` for usage errors (unknown commands, bad arguments).
+pub const EXIT_USAGE: i32 = 2;
+
+/// Converts whatever a handler returns into a process exit code.
+///
+/// Handlers returning `LuhTwin<()>` exit with `EXIT_SUCCESS`, handlers that
+/// want a specific status can return `LuhTwin<i32>` (or `u8`) instead.
+///
+/// ```ignore
+/// Command::new("check").handler(|args: &ParsedArgs| {
+///     if args.flag("strict") { Ok(3) } else { Ok(0) }
+/// });
+/// ```
+pub trait IntoExitCode {
+    /// Turn the value into an exit code.
+    fn into_exit_code(self) -> i32;
+}
+
+impl IntoExitCode for () {
+    fn into_exit_code(self) -> i32 {
+        EXIT_SUCCESS
+    }
+}
+
+impl IntoExitCode for i32 {
+    fn into_exit_code(self) -> i32 {
+        self
+    }
+}
+
+impl IntoExitCode for u8 {
+    fn into_exit_code(self) -> i32 {
+        self as i32
+    }
+}
 
 /// Can either be Flag, Option, Positional or Variadic
 ///   - Flag (being -h, --help)
@@ -192,6 +262,50 @@ pub enum ArgType {
     Variadic,
 }
 
+/// What kind of value an argument expects, set with [`Arg::value_hint`].
+///
+/// Used by tab completion in the interactive shell (`repl` feature), which
+/// completes paths for `FilePath`, `DirPath` and `ExecutablePath`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValueHint {
+    /// Path to a file
+    FilePath,
+    /// Path to a directory
+    DirPath,
+    /// Path to an executable (or the name of one on `PATH`)
+    ExecutablePath,
+    /// Host name or address
+    Hostname,
+    /// URL
+    Url,
+    /// User name
+    Username,
+    /// Anything else, the default
+    Other,
+}
+
+/// A check run on every value of an argument, see [`Arg::validator`].
+///
+/// Returns why the value is invalid, which ends up in the error naming the arg and the value.
+pub type Validator = Rc<dyn Fn(&str) -> Result<(), String>>;
+
+/// A filesystem check on the value of a path argument, see [`Arg::must_exist`] and friends.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PathCheck {
+    /// The path exists
+    MustExist,
+    /// The path is an existing file
+    IsFile,
+    /// The path is an existing directory
+    IsDir,
+    /// The directory the path is in exists
+    ParentExists,
+    /// The path has one of these extensions (without the dot, compared ignoring ASCII case)
+    Extension(Vec<String>),
+    /// Nothing exists at the path yet
+    NotExists,
+}
+
 /// Represents a single command-line argument.
 ///
 /// `Arg` is the core building block for defining CLI arguments in `luhcli`.
@@ -228,9 +342,40 @@ pub enum ArgType {
 /// - [`Arg::takes_value`] – Mark the argument as an option that takes a value.
 /// - [`Arg::depends_on`] – Add dependencies on other arguments.
 /// - [`Arg::conflicts_with`] – Add conflicts with other arguments.
+/// - [`Arg::required_unless_present`] – Require the argument unless another is specified.
+/// - [`Arg::required_if_eq`] – Require the argument when another has a given value.
+/// - [`Arg::required_if_any`] – Require the argument when any of several conditions holds.
+/// - [`Arg::requires_if`] – Require another argument when this one has a given value.
+/// - [`Arg::depends_on_value`] – Add a dependency on another argument having a given value.
+/// - [`Arg::env`] – Read the argument from an environment variable.
 /// - [`Arg::default_value`] – Set a default value.
-/// - [`Arg::possible_values`] – Restrict allowed values.
+/// - [`Arg::default_value_if`] – Set a default value depending on another argument's value.
+/// - [`Arg::default_missing_value`] – Set the value used when an option is given without one.
+/// - [`Arg::possible_values`] – Restrict allowed values, see [`PossibleValue`].
+/// - [`Arg::value_enum`] – Take the variants of a [`ValueEnum`] as possible values.
+/// - [`Arg::ignore_case`] – Match possible values ignoring ASCII case.
+/// - [`Arg::validator`] – Check values with a function of your own.
+/// - [`Arg::matches`] – Check values against a regular expression (`regex` feature).
+/// - [`Arg::length`] – Limit the length of values.
+/// - [`Arg::range`] – Require values to be numbers in a range (e.g. `1..=65535`).
+/// - [`Arg::value_delimiter`] – Split values into several pieces (e.g. `--tags a,b,c`).
+/// - [`Arg::key_value`] – Take repeated `KEY=VALUE` pairs (e.g. `-D name=value`).
+/// - [`Arg::key_separator`] – Set the separator between keys and values.
+/// - [`Arg::duplicate_keys`] – Choose what happens when a key is given twice.
+/// - [`Arg::allowed_keys`] – Restrict allowed keys.
+/// - [`Arg::raw`] – Accept values that are not valid UTF-8, such as file names.
+/// - [`Arg::value_name`] – Set the name of the value shown in help (e.g. `<FILE>`).
+/// - [`Arg::value_hint`] – Tell completion what kind of value is expected.
+/// - [`Arg::must_exist`] – Require the path to exist.
+/// - [`Arg::is_file`] – Require the path to be an existing file.
+/// - [`Arg::is_dir`] – Require the path to be an existing directory.
+/// - [`Arg::parent_exists`] – Require the directory the path is in to exist.
+/// - [`Arg::extension`] – Require the path to have one of the given extensions.
+/// - [`Arg::not_exists`] – Require that nothing exists at the path yet.
+/// - [`Arg::prompt`] – Ask for the argument on a terminal when it is missing (`prompt` feature).
+/// - [`Arg::secret`] – Hide the answer to the prompt (`prompt` feature).
 /// - [`Arg::when`] – Define conditional sub-arguments.
+/// - [`Arg::when_variant`] – Define conditional sub-arguments keyed by a [`ValueEnum`] variant.
 #[derive(Clone)]
 pub struct Arg {
     /// Name of the argument (used internally and as default for long option)
@@ -252,9 +397,129 @@ pub struct Arg {
     /// Optional default value for the argument
     pub default_value: Option<String>,
     /// List of allowed values for this argument
-    pub possible_values: Vec<String>,
+    pub possible_values: Vec<PossibleValue>,
+    /// Whether possible values (and their aliases) match ignoring ASCII case
+    pub ignore_case: bool,
+    /// Checks run on every value of this argument
+    pub validators: Vec<Validator>,
     /// Conditional sub-arguments that apply when this argument has a specific value
     pub children: Vec<ArgChain>,
+    /// Arguments whose presence makes this argument optional (it is required otherwise)
+    pub required_unless: Vec<String>,
+    /// `(arg, value)` conditions, any of which makes this argument required
+    pub required_if: Vec<(String, String)>,
+    /// `(value, arg)` pairs: when this argument has `value`, `arg` must be specified
+    pub requires_if: Vec<(String, String)>,
+    /// `(arg, value)` pairs: this argument requires `arg` to be specified with `value`
+    pub depends_on_value: Vec<(String, String)>,
+    /// `(arg, value, default)` conditional defaults, the first matching one wins over `default_value`
+    pub default_value_if: Vec<(String, String, String)>,
+    /// Value used when the option is given without one (e.g. `--color` instead of `--color=always`)
+    pub default_missing_value: Option<String>,
+    /// Environment variable read when the argument is not given on the command line
+    pub env: Option<String>,
+    /// Whether values that are not valid UTF-8 are accepted (see `ParsedArgs::get_os`)
+    pub raw: bool,
+    /// Name of the value shown in help instead of the argument's name
+    pub value_name: Option<String>,
+    /// What kind of value the argument expects
+    pub value_hint: ValueHint,
+    /// Filesystem checks run on the value once the command is parsed
+    pub path_checks: Vec<PathCheck>,
+    /// Character splitting a value into several pieces (see `ParsedArgs::get_many`)
+    pub value_delimiter: Option<char>,
+    /// Separator between keys and values of a `KEY=VALUE` arg (see `ParsedArgs::get_map`)
+    pub key_value: Option<char>,
+    /// What happens when a key of a `KEY=VALUE` arg is given more than once
+    pub duplicate_keys: DuplicateKeys,
+    /// List of allowed keys for a `KEY=VALUE` arg
+    pub allowed_keys: Vec<String>,
+    /// Message shown when asking for a missing required argument on a terminal
+    #[cfg(feature = "prompt")]
+    pub prompt: Option<String>,
+    /// Whether the answer to the prompt is read without echoing it
+    #[cfg(feature = "prompt")]
+    pub secret: bool,
+}
+
+/// A value an argument accepts, see [`Arg::possible_values`].
+///
+/// Strings convert into possible values, so `.possible_values(["get", "set"])`
+/// keeps working. Build one to explain the value, give it aliases or hide it.
+///
+/// ```ignore
+/// Arg::positional("action", 0).possible_values([
+///     PossibleValue::new("get").help("print a key"),
+///     PossibleValue::new("list").alias("ls").help("print every key"),
+///     PossibleValue::new("dump").hide(true),
+/// ])
+/// ```
+///
+/// # Provided Methods
+///
+/// - [`PossibleValue::new`] – Create a possible value.
+/// - [`PossibleValue::help`] – Set the help shown next to the value.
+/// - [`PossibleValue::alias`] – Accept another spelling of the value.
+/// - [`PossibleValue::hide`] – Leave the value out of help, errors and completions.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PossibleValue {
+    /// The value, as stored in `ParsedArgs` whichever spelling was given
+    pub name: String,
+    /// Help message describing the value
+    pub help: String,
+    /// Other spellings accepted for the value
+    pub aliases: Vec<String>,
+    /// Whether the value is left out of help, error messages and completions
+    pub hidden: bool,
+}
+
+impl PossibleValue {
+    /// Create a possible value.
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            help: String::new(),
+            aliases: Vec::new(),
+            hidden: false,
+        }
+    }
+    
+    /// Set the help shown next to the value.
+    pub fn help(mut self, h: impl Into<String>) -> Self {
+        self.help = h.into();
+        self
+    }
+    
+    /// Accept another spelling of the value, stored under the value's own name.
+    pub fn alias(mut self, alias: impl Into<String>) -> Self {
+        self.aliases.push(alias.into());
+        self
+    }
+    
+    /// Leave the value out of help, error messages and completions, it is still accepted.
+    pub fn hide(mut self, hidden: bool) -> Self {
+        self.hidden = hidden;
+        self
+    }
+    
+    /// Whether `value` is this value or one of its aliases.
+    fn is_match(&self, value: &str, ignore_case: bool) -> bool {
+        std::iter::once(&self.name).chain(&self.aliases).any(|name| {
+            if ignore_case { name.eq_ignore_ascii_case(value) } else { name == value }
+        })
+    }
+}
+
+impl From<&str> for PossibleValue {
+    fn from(name: &str) -> Self {
+        Self::new(name)
+    }
+}
+
+impl From<String> for PossibleValue {
+    fn from(name: String) -> Self {
+        Self::new(name)
+    }
 }
 
 /// Represents a set of sub-arguments that are only active when the parent `Arg` has a specific value.
@@ -274,7 +539,7 @@ pub struct Arg {
 /// ```
 #[derive(Clone)]
 pub struct ArgChain {
-    /// The value of the parent argument that triggers this chain
+    /// The value of the parent argument that triggers this chain (given directly or through an alias)
     pub when_value: String,
     /// Arguments that become active when the parent argument matches `when_value`
     pub args: Vec<Arg>,
@@ -295,41 +560,47 @@ impl Arg {
             conflicts_with: Vec::new(),
             default_value: None,
             possible_values: Vec::new(),
+            ignore_case: false,
+            validators: Vec::new(),
             children: Vec::new(),
+            required_unless: Vec::new(),
+            required_if: Vec::new(),
+            requires_if: Vec::new(),
+            depends_on_value: Vec::new(),
+            default_value_if: Vec::new(),
+            default_missing_value: None,
+            env: None,
+            raw: false,
+            value_name: None,
+            value_hint: ValueHint::Other,
+            path_checks: Vec::new(),
+            value_delimiter: None,
+            key_value: None,
+            duplicate_keys: DuplicateKeys::Last,
+            allowed_keys: Vec::new(),
+            #[cfg(feature = "prompt")]
+            prompt: None,
+            #[cfg(feature = "prompt")]
+            secret: false,
         }
     }
     
     /// Create a new positional argument.
     pub fn positional(name: impl Into<String>, index: usize) -> Self {
         Self {
-            name: name.into(),
-            short: None,
             long: None,
-            help: String::new(),
             required: true,
             arg_type: ArgType::Positional { index },
-            depends_on: Vec::new(),
-            conflicts_with: Vec::new(),
-            default_value: None,
-            possible_values: Vec::new(),
-            children: Vec::new(),
+            ..Self::new(name)
         }
     }
     
     /// Create a new variadic argument.
     pub fn variadic(name: impl Into<String>) -> Self {
         Self {
-            name: name.into(),
-            short: None,
             long: None,
-            help: String::new(),
-            required: false,
             arg_type: ArgType::Variadic,
-            depends_on: Vec::new(),
-            conflicts_with: Vec::new(),
-            default_value: None,
-            possible_values: Vec::new(),
-            children: Vec::new(),
+            ..Self::new(name)
         }
     }
     
@@ -363,138 +634,1131 @@ impl Arg {
         self
     }
     
-    /// Add dependencies on other arguments.
-    pub fn depends_on(mut self, arg: impl Into<String>) -> Self {
-        self.depends_on.push(arg.into());
+    /// Split values at `delimiter`, e.g. `--tags a,b,c` into `a`, `b` and `c`.
+    ///
+    /// Every piece is checked against `possible_values` and the pieces are read
+    /// with [`ParsedArgs::get_many`]. A backslash escapes the delimiter (`a\,b`
+    /// is the single piece `a,b`) and itself (`\\`). Variadic args split each
+    /// of their values, config file arrays give the pieces directly.
+    ///
+    /// ```ignore
+    /// Arg::new("tags").takes_value().value_delimiter(',')
+    /// ```
+    pub fn value_delimiter(mut self, delimiter: char) -> Self {
+        self.value_delimiter = Some(delimiter);
         self
     }
     
-    /// Add conflicts with other arguments.
-    pub fn conflicts_with(mut self, arg: impl Into<String>) -> Self {
-        self.conflicts_with.push(arg.into());
+    /// Mark the argument as an option taking `KEY=VALUE` pairs, which may be given repeatedly.
+    ///
+    /// The pairs are read with [`ParsedArgs::get_map`], [`ParsedArgs::get`] holds
+    /// the last one as given. Combined with [`Arg::value_delimiter`] a single
+    /// occurrence can hold several pairs (`--label a=1,b=2`), which is also how
+    /// they are given through an environment variable. Config file arrays give
+    /// one pair per element.
+    ///
+    /// ```ignore
+    /// // -D name=value -D debug=1
+    /// Arg::new("define").short('D').key_value()
+    /// ```
+    pub fn key_value(mut self) -> Self {
+        self.arg_type = ArgType::Option;
+        self.key_value.get_or_insert('=');
         self
     }
     
-    /// Set a default value.
-    pub fn default_value(mut self, val: impl Into<String>) -> Self {
-        self.default_value = Some(val.into());
+    /// Set the separator between keys and values (`=` by default), implies [`Arg::key_value`].
+    pub fn key_separator(mut self, separator: char) -> Self {
+        self = self.key_value();
+        self.key_value = Some(separator);
         self
     }
     
-    /// Restrict allowed values.
-    pub fn possible_values<I, S>(mut self, values: I) -> Self 
+    /// Choose what happens when a key is given more than once (later pairs win by default).
+    pub fn duplicate_keys(mut self, policy: DuplicateKeys) -> Self {
+        self.duplicate_keys = policy;
+        self
+    }
+    
+    /// Restrict the keys of a [`Arg::key_value`] arg to the given ones.
+    pub fn allowed_keys<I, S>(mut self, keys: I) -> Self
     where
         I: IntoIterator<Item = S>,
         S: Into<String>,
     {
-        self.possible_values = values.into_iter().map(Into::into).collect();
+        self.allowed_keys = keys.into_iter().map(Into::into).collect();
         self
     }
     
-    /// Define conditional sub-arguments.
-    pub fn when<I>(mut self, value: impl Into<String>, args: I) -> Self 
+    /// Accept values that are not valid UTF-8, such as arbitrary file names.
+    ///
+    /// The exact value is read with [`ParsedArgs::get_os`] or [`ParsedArgs::get_path`]
+    /// (or [`ParsedArgs::variadic_os`]), [`ParsedArgs::get`] holds a lossy copy.
+    /// Other args reject values that aren't UTF-8 with an error.
+    ///
+    /// ```ignore
+    /// Arg::positional("file", 0).raw()
+    /// ```
+    pub fn raw(mut self) -> Self {
+        self.raw = true;
+        self
+    }
+    
+    /// Set the name of the value shown in help, `--config <FILE>` instead of `--config <config>`.
+    pub fn value_name(mut self, name: impl Into<String>) -> Self {
+        self.value_name = Some(name.into());
+        self
+    }
+    
+    /// Tell completion what kind of value is expected, see [`ValueHint`].
+    ///
+    /// ```ignore
+    /// Arg::new("config").takes_value().value_name("FILE").value_hint(ValueHint::FilePath)
+    /// ```
+    pub fn value_hint(mut self, hint: ValueHint) -> Self {
+        self.value_hint = hint;
+        self
+    }
+    
+    /// Require the path to exist.
+    ///
+    /// Path checks run on every value of the argument, including defaults and
+    /// each piece of a delimited value, and fail naming the argument and the path.
+    ///
+    /// ```ignore
+    /// Arg::positional("input", 0).must_exist()
+    /// ```
+    pub fn must_exist(mut self) -> Self {
+        self.path_checks.push(PathCheck::MustExist);
+        self
+    }
+    
+    /// Require the path to be an existing file.
+    pub fn is_file(mut self) -> Self {
+        self.path_checks.push(PathCheck::IsFile);
+        self
+    }
+    
+    /// Require the path to be an existing directory.
+    pub fn is_dir(mut self) -> Self {
+        self.path_checks.push(PathCheck::IsDir);
+        self
+    }
+    
+    /// Require the directory the path is in to exist, e.g. for an output file.
+    pub fn parent_exists(mut self) -> Self {
+        self.path_checks.push(PathCheck::ParentExists);
+        self
+    }
+    
+    /// Require the path to have one of the given extensions.
+    ///
+    /// ```ignore
+    /// Arg::new("config").takes_value().extension(["json", "toml"])
+    /// ```
+    pub fn extension<I, S>(mut self, extensions: I) -> Self
     where
-        I: IntoIterator<Item = Arg>,
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
     {
-        self.children.push(ArgChain {
-            when_value: value.into(),
-            args: args.into_iter().collect(),
-        });
+        let extensions = extensions.into_iter()
+            .map(|e| e.into().trim_start_matches('.').to_string())
+            .collect();
+        self.path_checks.push(PathCheck::Extension(extensions));
         self
     }
-}
-
-/// Represents the result of parsing command-line arguments with `luhcli`.
-///  
-/// Stores values for options, flags, positional arguments, and variadic arguments.
-///
-/// Provides convenient accessors for retrieving arguments after parsing.
-///
-/// # Examples
-///
-/// ```ignore
-/// use luhcli::ParsedArgs;
-///
-/// // Imagine `parsed` is the result of parsing CLI arguments
-/// let parsed: ParsedArgs = /* parsing logic */;
-///
-/// // Retrieve a value of an option
-/// if let Some(output) = parsed.get("output") {
-///     println!("Output file: {}", output);
-/// }
-///
-/// // Check if a flag was set
-/// if parsed.flag("verbose") {
-///     println!("Verbose mode enabled");
-/// }
-///
-/// // Access positional arguments
-/// for arg in parsed.positional() {
-///     println!("Positional argument: {}", arg);
-/// }
-///
-/// // Access variadic arguments
-/// for arg in parsed.variadic() {
-///     println!("Variadic argument: {}", arg);
-/// }
-/// ```
-/// 
-/// # Provided Methods
-///
-/// - [`ParsedArgs::get`] – Retrieve the value of an option by name.
-/// - [`ParsedArgs::flag`] – Check if a flag was set.
-/// - [`ParsedArgs::positional`] – Get a slice of all positional arguments.
-/// - [`ParsedArgs::variadic`] – Get a slice of all variadic arguments.
-/// - [`ParsedArgs::pos`] – Retrieve a positional argument by its index.
-#[derive(Debug, Clone)]
-pub struct ParsedArgs {
-    /// Values for options (arguments that take a value)
-    values: HashMap<String, String>,
-    /// Flags (true if present, false otherwise)
-    flags: HashMap<String, bool>,
-    /// Positional arguments (ordered)
-    positional: Vec<String>,
-    /// Variadic arguments (remaining arguments after positional)
-    variadic: Vec<String>,
-}
-
-impl ParsedArgs {
-    /// Retrieve the value of an option by name.
-    pub fn get(&self, name: &str) -> Option<&String> {
-        self.values.get(name)
+    
+    /// Require that nothing exists at the path yet, e.g. so an output file isn't overwritten.
+    pub fn not_exists(mut self) -> Self {
+        self.path_checks.push(PathCheck::NotExists);
+        self
     }
     
-    /// Check if a flag was set.
-    pub fn flag(&self, name: &str) -> bool {
-        self.flags.get(name).copied().unwrap_or(false)
+    /// Add dependencies on other arguments.
+    pub fn depends_on(mut self, arg: impl Into<String>) -> Self {
+        self.depends_on.push(arg.into());
+        self
     }
     
-    /// Get a slice of all positional arguments.
-    pub fn positional(&self) -> &[String] {
-        &self.positional
+    /// Add conflicts with other arguments.
+    pub fn conflicts_with(mut self, arg: impl Into<String>) -> Self {
+        self.conflicts_with.push(arg.into());
+        self
     }
     
-    /// Get a slice of all variadic arguments.
-    pub fn variadic(&self) -> &[String] {
-        &self.variadic
+    /// Require the argument unless `arg` is specified (several calls: unless any of them is).
+    ///
+    /// ```ignore
+    /// Arg::new("token").takes_value().required_unless_present("config")
+    /// ```
+    pub fn required_unless_present(mut self, arg: impl Into<String>) -> Self {
+        self.required_unless.push(arg.into());
+        self
     }
     
-    /// Retrieve a positional argument by its index.
-    pub fn pos(&self, index: usize) -> Option<&String> {
-        self.positional.get(index)
+    /// Require the argument when `arg` has the value `value`.
+    ///
+    /// Defaults count: with `format` defaulting to `csv`, the delimiter is
+    /// required unless another format is given.
+    ///
+    /// ```ignore
+    /// Arg::new("delimiter").takes_value().required_if_eq("format", "csv")
+    /// ```
+    pub fn required_if_eq(mut self, arg: impl Into<String>, value: impl Into<String>) -> Self {
+        self.required_if.push((arg.into(), value.into()));
+        self
     }
-}
-
-/// Represents a single CLI command in `luhcli`.
-///  
-/// Commands can have:
-/// - Arguments (`Arg`)
-/// - Subcommands (`Command`)
-/// - A handler function executed when the command is invoked.
-///
-/// # Examples
-///
-/// ```ignore
+    
+    /// Require the argument when any of the `(arg, value)` conditions holds.
+    pub fn required_if_any<I, A, V>(mut self, conditions: I) -> Self
+    where
+        I: IntoIterator<Item = (A, V)>,
+        A: Into<String>,
+        V: Into<String>,
+    {
+        self.required_if.extend(conditions.into_iter().map(|(a, v)| (a.into(), v.into())));
+        self
+    }
+    
+    /// When this argument has the value `value`, require `arg` to be specified too.
+    ///
+    /// Both this argument's default and `arg`'s default count.
+    ///
+    /// ```ignore
+    /// Arg::new("format").takes_value().requires_if("csv", "delimiter")
+    /// ```
+    pub fn requires_if(mut self, value: impl Into<String>, arg: impl Into<String>) -> Self {
+        self.requires_if.push((value.into(), arg.into()));
+        self
+    }
+    
+    /// Add a dependency on another argument having a specific value.
+    ///
+    /// Only applies when this argument is given, `arg`'s default counts.
+    ///
+    /// ```ignore
+    /// // --delimiter only makes sense with --format=csv
+    /// Arg::new("delimiter").takes_value().depends_on_value("format", "csv")
+    /// ```
+    pub fn depends_on_value(mut self, arg: impl Into<String>, value: impl Into<String>) -> Self {
+        self.depends_on_value.push((arg.into(), value.into()));
+        self
+    }
+    
+    /// Read the argument from an environment variable when it is not given on the command line.
+    ///
+    /// Precedence is command line > environment > config file > default. Flags
+    /// are turned on by any value other than `0`, `false`, `no`, `off` or empty,
+    /// variadic args split the variable on whitespace.
+    ///
+    /// ```ignore
+    /// Arg::new("token").takes_value().env("MYAPP_TOKEN")
+    /// ```
+    pub fn env(mut self, var: impl Into<String>) -> Self {
+        self.env = Some(var.into());
+        self
+    }
+    
+    /// Set a default value, used whenever the argument is not given.
+    pub fn default_value(mut self, val: impl Into<String>) -> Self {
+        self.default_value = Some(val.into());
+        self
+    }
+    
+    /// Set a default value used only when `arg` has the value `value`.
+    ///
+    /// Conditional defaults are tried in order and win over [`Arg::default_value`].
+    ///
+    /// ```ignore
+    /// Arg::new("level").takes_value().default_value("3").default_value_if("mode", "fast", "1")
+    /// ```
+    pub fn default_value_if(
+        mut self,
+        arg: impl Into<String>,
+        value: impl Into<String>,
+        default: impl Into<String>,
+    ) -> Self {
+        self.default_value_if.push((arg.into(), value.into(), default.into()));
+        self
+    }
+    
+    /// Set the value used when the option is given without one.
+    ///
+    /// ```ignore
+    /// // `--color` means `--color always`, leaving it out means `auto`
+    /// Arg::new("color").takes_value().default_value("auto").default_missing_value("always")
+    /// ```
+    pub fn default_missing_value(mut self, val: impl Into<String>) -> Self {
+        self.default_missing_value = Some(val.into());
+        self
+    }
+    
+    /// Restrict allowed values, given as strings or as [`PossibleValue`]s.
+    ///
+    /// A value given through an alias (or in another case, see [`Arg::ignore_case`])
+    /// is stored under the possible value's name.
+    pub fn possible_values<I, S>(mut self, values: I) -> Self 
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<PossibleValue>,
+    {
+        self.possible_values = values.into_iter().map(Into::into).collect();
+        self
+    }
+    
+    /// Take the variants of `T` as possible values, read them back with [`ParsedArgs::get_enum`].
+    ///
+    /// ```ignore
+    /// Arg::new("mode").takes_value().value_enum::<Mode>()
+    /// ```
+    pub fn value_enum<T: ValueEnum>(self) -> Self {
+        self.possible_values(T::value_variants().iter().map(T::to_possible_value))
+    }
+    
+    /// Match possible values (and their aliases) ignoring ASCII case, e.g. `--format JSON`.
+    pub fn ignore_case(mut self, ignore: bool) -> Self {
+        self.ignore_case = ignore;
+        self
+    }
+    
+    /// Check values with a function returning why a value is invalid.
+    ///
+    /// Validators run on every value: options, positionals, each variadic
+    /// value and each piece of a delimited value, wherever it came from.
+    /// Defaults are checked by [`CliApp::validate`].
+    ///
+    /// ```ignore
+    /// Arg::new("name").takes_value().validator(|v| {
+    ///     if v.chars().all(char::is_alphanumeric) { Ok(()) } else { Err("must be alphanumeric".into()) }
+    /// })
+    /// ```
+    pub fn validator<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&str) -> Result<(), String> + 'static,
+    {
+        self.validators.push(Rc::new(f));
+        self
+    }
+    
+    /// Check values against a regular expression, anchor it (`^...$`) to match whole values.
+    ///
+    /// ```ignore
+    /// Arg::new("tag").takes_value().matches(Regex::new(r"^v\d+\.\d+$").unwrap())
+    /// ```
+    #[cfg(feature = "regex")]
+    pub fn matches(self, regex: regex::Regex) -> Self {
+        self.validator(move |v| {
+            if regex.is_match(v) {
+                Ok(())
+            } else {
+                Err(format!("must match '{}'", regex.as_str()))
+            }
+        })
+    }
+    
+    /// Limit the length of values, in characters.
+    ///
+    /// ```ignore
+    /// Arg::new("name").takes_value().length(1..=32)
+    /// ```
+    pub fn length(self, range: impl RangeBounds<usize>) -> Self {
+        let bounds = (range.start_bound().cloned(), range.end_bound().cloned());
+        self.validator(move |v| {
+            if bounds.contains(&v.chars().count()) {
+                Ok(())
+            } else {
+                Err(format!("must be {} characters long", describe_bounds(&bounds)))
+            }
+        })
+    }
+    
+    /// Require values to be numbers in a range.
+    ///
+    /// ```ignore
+    /// Arg::new("port").takes_value().range(1..=65535)
+    /// ```
+    pub fn range<T>(self, range: impl RangeBounds<T>) -> Self
+    where
+        T: FromStr + PartialOrd + Display + Clone + 'static,
+    {
+        let bounds = (range.start_bound().cloned(), range.end_bound().cloned());
+        self.validator(move |v| match v.parse::<T>() {
+            Ok(n) if bounds.contains(&n) => Ok(()),
+            _ => Err(format!("must be a number {}", describe_bounds(&bounds))),
+        })
+    }
+    
+    /// Ask for the argument when it is required but missing and stdin is a terminal.
+    ///
+    /// Args with `possible_values` are picked from a numbered list, other answers
+    /// are checked like command line values and asked again when invalid. When
+    /// the input is not interactive the usual "missing argument" error is returned.
+    ///
+    /// ```ignore
+    /// Arg::new("api-key").takes_value().required(true).prompt("Enter API key").secret()
+    /// ```
+    #[cfg(feature = "prompt")]
+    pub fn prompt(mut self, message: impl Into<String>) -> Self {
+        self.prompt = Some(message.into());
+        self
+    }
+    
+    /// Read the answer to [`Arg::prompt`] without echoing it, for passwords and tokens.
+    #[cfg(feature = "prompt")]
+    pub fn secret(mut self) -> Self {
+        self.secret = true;
+        self
+    }
+    
+    /// The value from the environment or the config file, with where it came from.
+    fn layered_value(&self, config: Option<&ConfigSection>) -> Option<(Vec<String>, ValueSource, String)> {
+        if let Some(var) = &self.env {
+            if let Ok(value) = std::env::var(var) {
+                let values = match self.arg_type {
+                    ArgType::Variadic => value.split_whitespace().map(String::from).collect(),
+                    _ => vec![value],
+                };
+                return Some((values, ValueSource::Env, format!("environment variable {}", var)));
+            }
+        }
+        
+        let config = config?;
+        config.values.get(&self.name)
+            .map(|values| (values.clone(), ValueSource::ConfigFile, config.origin.clone()))
+    }
+    
+    /// Check a value against `possible_values`, `origin` naming where it came
+    /// from when that isn't the command line.
+    ///
+    /// Returns the value with aliases replaced by the names of their possible values.
+    fn check_value(&self, value: &str, origin: Option<&str>) -> LuhTwin<String> {
+        if self.possible_values.is_empty() && self.validators.is_empty() {
+            return Ok(value.to_string());
+        }
+        
+        let from = origin.map(|o| format!(" (from {})", o)).unwrap_or_default();
+        let pieces = self.split_value(value);
+        let mut checked = Vec::with_capacity(pieces.len());
+        
+        for piece in &pieces {
+            let piece = if self.possible_values.is_empty() {
+                piece
+            } else if let Some(possible) = self.possible_value(piece) {
+                &possible.name
+            } else {
+                return Err(at!(
+                    "invalid value '{}' for '{}'{}. possible values: {}",
+                    piece,
+                    self.name,
+                    from,
+                    self.visible_values().join(", ")
+                ).into());
+            };
+            
+            if let Err(reason) = self.run_validators(piece) {
+                return Err(at!("invalid value '{}' for '{}'{}: {}", piece, self.name, from, reason).into());
+            }
+            checked.push(piece.clone());
+        }
+        
+        if checked == pieces {
+            Ok(value.to_string())
+        } else {
+            Ok(self.join_values(&checked))
+        }
+    }
+    
+    /// The possible value `value` spells, if any.
+    fn possible_value(&self, value: &str) -> Option<&PossibleValue> {
+        self.possible_values.iter().find(|p| p.is_match(value, self.ignore_case))
+    }
+    
+    /// Names of the possible values that aren't hidden.
+    fn visible_values(&self) -> Vec<&str> {
+        self.possible_values.iter()
+            .filter(|p| !p.hidden)
+            .map(|p| p.name.as_str())
+            .collect()
+    }
+    
+    /// The visible possible values as shown in help (` [get|set]`), empty without any.
+    fn value_list(&self) -> String {
+        let shown = self.visible_values();
+        if shown.is_empty() {
+            String::new()
+        } else {
+            format!(" [{}]", shown.join("|"))
+        }
+    }
+    
+    /// Whether `value` triggers the `when` chain for `when_value`, directly or through an alias.
+    fn is_when_value(&self, value: &str, when_value: &str) -> bool {
+        value == when_value
+            || self.possible_value(value).is_some_and(|p| p.is_match(when_value, self.ignore_case))
+    }
+    
+    /// The first complaint of the validators about a single value.
+    fn run_validators(&self, value: &str) -> Result<(), String> {
+        self.validators.iter().try_for_each(|validator| validator(value))
+    }
+    
+    /// The name of the value in help and messages.
+    fn placeholder(&self) -> &str {
+        self.value_name.as_deref().unwrap_or(&self.name)
+    }
+    
+    /// The pieces of a value, split at the `value_delimiter` (the whole value without one).
+    fn split_value(&self, value: &str) -> Vec<String> {
+        let Some(delimiter) = self.value_delimiter else {
+            return vec![value.to_string()];
+        };
+        
+        let mut pieces = vec![String::new()];
+        let mut chars = value.chars();
+        while let Some(c) = chars.next() {
+            let piece = pieces.last_mut().expect("at least one piece");
+            match c {
+                '\\' => match chars.next() {
+                    Some(next) if next == delimiter || next == '\\' => piece.push(next),
+                    Some(next) => {
+                        piece.push(c);
+                        piece.push(next);
+                    }
+                    None => piece.push(c),
+                },
+                _ if c == delimiter => pieces.push(String::new()),
+                _ => piece.push(c),
+            }
+        }
+        pieces
+    }
+    
+    /// The pairs of a `KEY=VALUE` arg given all its occurrences, in the order their keys first appear.
+    fn key_values(&self, separator: char, occurrences: &[String]) -> LuhTwin<Vec<(String, String)>> {
+        let mut pairs: Vec<(String, String)> = Vec::new();
+        
+        for pair in occurrences.iter().flat_map(|v| self.split_value(v)) {
+            let Some((key, value)) = pair.split_once(separator).filter(|(key, _)| !key.is_empty()) else {
+                return Err(at!(
+                    "invalid value '{}' for '{}': expected KEY{}VALUE",
+                    pair,
+                    self.name,
+                    separator
+                ).into());
+            };
+            
+            if !self.allowed_keys.is_empty() && !self.allowed_keys.iter().any(|k| k == key) {
+                return Err(at!(
+                    "invalid key '{}' for '{}'. allowed keys: {}",
+                    key,
+                    self.name,
+                    self.allowed_keys.join(", ")
+                ).into());
+            }
+            
+            match (pairs.iter_mut().find(|(k, _)| k == key), self.duplicate_keys) {
+                (None, _) => pairs.push((key.to_string(), value.to_string())),
+                (Some(existing), DuplicateKeys::Last) => existing.1 = value.to_string(),
+                (Some(_), DuplicateKeys::First) => {}
+                (Some(_), DuplicateKeys::Error) => {
+                    return Err(at!("key '{}' of '{}' is given more than once", key, self.name).into());
+                }
+            }
+        }
+        
+        Ok(pairs)
+    }
+    
+    /// The one value given by the environment or a config file, config arrays
+    /// being joined for args with a `value_delimiter`.
+    fn single_value(&self, values: &[String]) -> Option<String> {
+        match (values, self.value_delimiter) {
+            ([value], _) => Some(value.clone()),
+            (_, Some(_)) => Some(self.join_values(values)),
+            _ => None,
+        }
+    }
+    
+    /// Pieces joined back into one value, escaped so `split_value` gives them back.
+    fn join_values(&self, pieces: &[String]) -> String {
+        let Some(delimiter) = self.value_delimiter else {
+            return pieces.concat();
+        };
+        
+        let escaped: Vec<_> = pieces.iter()
+            .map(|v| v.replace('\\', "\\\\").replace(delimiter, &format!("\\{}", delimiter)))
+            .collect();
+        escaped.join(&delimiter.to_string())
+    }
+    
+    /// The default that applies given the values parsed so far, if any.
+    fn default_for(&self, values: &HashMap<String, String>) -> Option<&String> {
+        self.default_value_if.iter()
+            .find(|(arg, value, _)| values.get(arg) == Some(value))
+            .map(|(_, _, default)| default)
+            .or(self.default_value.as_ref())
+    }
+    
+    /// Define conditional sub-arguments.
+    pub fn when<I>(mut self, value: impl Into<String>, args: I) -> Self 
+    where
+        I: IntoIterator<Item = Arg>,
+    {
+        self.children.push(ArgChain {
+            when_value: value.into(),
+            args: args.into_iter().collect(),
+        });
+        self
+    }
+    
+    /// Define conditional sub-arguments, active when the arg's value is `variant`.
+    ///
+    /// ```ignore
+    /// Arg::positional("mode", 0).value_enum::<Mode>()
+    ///     .when_variant(Mode::Advanced, [Arg::new("config").takes_value()])
+    /// ```
+    pub fn when_variant<T, I>(self, variant: T, args: I) -> Self
+    where
+        T: ValueEnum,
+        I: IntoIterator<Item = Arg>,
+    {
+        self.when(variant.to_possible_value().name, args)
+    }
+}
+
+/// A named set of arguments that are checked together.
+///
+/// By default at most one member of a group may be given; use
+/// [`ArgGroup::required`] to demand at least one and [`ArgGroup::multiple`] to
+/// allow several. Groups can conflict with or require other args or groups, and
+/// an `Arg` may name a group in its own `depends_on`/`conflicts_with`.
+///
+/// # Examples
+///
+/// ```ignore
+/// use luhcli::{Arg, ArgGroup, Command};
+///
+/// // exactly one input source
+/// let cmd = Command::new("import")
+///     .arg(Arg::new("file").takes_value())
+///     .arg(Arg::new("url").takes_value())
+///     .arg(Arg::new("stdin"))
+///     .group(ArgGroup::new("input").args(["file", "url", "stdin"]).required(true));
+///
+/// // at least one change
+/// let cmd = Command::new("tags")
+///     .arg(Arg::new("add").takes_value())
+///     .arg(Arg::new("remove").takes_value())
+///     .group(ArgGroup::new("changes").args(["add", "remove"]).required(true).multiple(true));
+/// ```
+///
+/// # Provided Methods
+///
+/// - [`ArgGroup::new`] – Create a new, empty group.
+/// - [`ArgGroup::arg`] – Add a member arg by name.
+/// - [`ArgGroup::args`] – Add several member args by name.
+/// - [`ArgGroup::help`] – Set the help message.
+/// - [`ArgGroup::required`] – Require at least one member to be given.
+/// - [`ArgGroup::multiple`] – Allow more than one member to be given.
+/// - [`ArgGroup::conflicts_with`] – Add a conflicting arg or group.
+/// - [`ArgGroup::requires`] – Add an arg or group required whenever the group is used.
+#[derive(Clone)]
+pub struct ArgGroup {
+    /// Name of the group (can be used in `depends_on`/`conflicts_with`)
+    pub name: String,
+    /// Names of the member args
+    pub args: Vec<String>,
+    /// Help message describing the group
+    pub help: String,
+    /// Whether at least one member must be given
+    pub required: bool,
+    /// Whether more than one member may be given
+    pub multiple: bool,
+    /// Args or groups that cannot be used together with this group
+    pub conflicts_with: Vec<String>,
+    /// Args or groups that must be given whenever this group is used
+    pub requires: Vec<String>,
+}
+
+impl ArgGroup {
+    /// Create a new, empty group.
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            args: Vec::new(),
+            help: String::new(),
+            required: false,
+            multiple: false,
+            conflicts_with: Vec::new(),
+            requires: Vec::new(),
+        }
+    }
+
+    /// Add a member arg by name.
+    pub fn arg(mut self, name: impl Into<String>) -> Self {
+        self.args.push(name.into());
+        self
+    }
+
+    /// Add several member args by name.
+    pub fn args<I, S>(mut self, names: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.args.extend(names.into_iter().map(Into::into));
+        self
+    }
+
+    /// Set the help message.
+    pub fn help(mut self, h: impl Into<String>) -> Self {
+        self.help = h.into();
+        self
+    }
+
+    /// Require at least one member to be given.
+    pub fn required(mut self, r: bool) -> Self {
+        self.required = r;
+        self
+    }
+
+    /// Allow more than one member to be given.
+    pub fn multiple(mut self, m: bool) -> Self {
+        self.multiple = m;
+        self
+    }
+
+    /// Add a conflicting arg or group.
+    pub fn conflicts_with(mut self, name: impl Into<String>) -> Self {
+        self.conflicts_with.push(name.into());
+        self
+    }
+
+    /// Add an arg or group required whenever the group is used.
+    pub fn requires(mut self, name: impl Into<String>) -> Self {
+        self.requires.push(name.into());
+        self
+    }
+
+    /// Describe the group's rule and members for errors and help (e.g. `exactly one of --file, --url`).
+    fn describe(&self, args: &[Arg]) -> String {
+        let rule = match (self.required, self.multiple) {
+            (true, false) => "exactly one of",
+            (true, true) => "at least one of",
+            (false, false) => "at most one of",
+            (false, true) => "any of",
+        };
+        let members: Vec<_> = self.args.iter().map(|name| display_name(args, name)).collect();
+        format!("{} {}", rule, members.join(", "))
+    }
+}
+
+/// How an arg is written on the command line (`--file`, `-f`, `<path>`), for messages.
+fn display_name(args: &[Arg], name: &str) -> String {
+    match args.iter().find(|a| a.name == name) {
+        Some(arg) => match (arg.arg_type, &arg.long, arg.short) {
+            (ArgType::Flag | ArgType::Option, Some(long), _) => format!("--{}", long),
+            (ArgType::Flag | ArgType::Option, None, Some(short)) => format!("-{}", short),
+            (ArgType::Variadic, _, _) => format!("<{}>...", arg.placeholder()),
+            _ => format!("<{}>", arg.placeholder()),
+        },
+        None => name.to_string(),
+    }
+}
+
+/// Where the value of a parsed argument came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValueSource {
+    /// Given on the command line
+    CommandLine,
+    /// Read from the environment variable set with `Arg::env`
+    Env,
+    /// Read from the configuration file (`config` feature)
+    ConfigFile,
+    /// Filled in from `default_value` / `default_value_if`
+    Default,
+    /// Typed in at the prompt set with `Arg::prompt` (`prompt` feature)
+    Prompt,
+}
+
+/// What happens when a key of a [`Arg::key_value`] arg is given more than once.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DuplicateKeys {
+    /// The last value given wins (e.g. `-D a=1 -D a=2` gives `a=2`)
+    Last,
+    /// The first value given wins
+    First,
+    /// Giving a key twice is an error
+    Error,
+}
+
+/// Represents the result of parsing command-line arguments with `luhcli`.
+///  
+/// Stores values for options, flags, positional arguments, and variadic arguments.
+///
+/// Provides convenient accessors for retrieving arguments after parsing.
+///
+/// # Examples
+///
+/// ```ignore
+/// use luhcli::ParsedArgs;
+///
+/// // Imagine `parsed` is the result of parsing CLI arguments
+/// let parsed: ParsedArgs = /* parsing logic */;
+///
+/// // Retrieve a value of an option
+/// if let Some(output) = parsed.get("output") {
+///     println!("Output file: {}", output);
+/// }
+///
+/// // Check if a flag was set
+/// if parsed.flag("verbose") {
+///     println!("Verbose mode enabled");
+/// }
+///
+/// // Access positional arguments
+/// for arg in parsed.positional() {
+///     println!("Positional argument: {}", arg);
+/// }
+///
+/// // Access variadic arguments
+/// for arg in parsed.variadic() {
+///     println!("Variadic argument: {}", arg);
+/// }
+/// ```
+/// 
+/// # Provided Methods
+///
+/// - [`ParsedArgs::get`] – Retrieve the value of an option by name.
+/// - [`ParsedArgs::get_enum`] – Retrieve the value of an option as a [`ValueEnum`] variant.
+/// - [`ParsedArgs::get_many`] – Retrieve the pieces of a value split at its delimiter.
+/// - [`ParsedArgs::get_map`] – Retrieve the pairs of a `KEY=VALUE` arg.
+/// - [`ParsedArgs::assignments`] – Get the make-style `NAME=value` arguments.
+/// - [`ParsedArgs::assignment`] – Retrieve the value of an assignment by name.
+/// - [`ParsedArgs::get_os`] – Retrieve the exact value of an argument as an `OsStr`.
+/// - [`ParsedArgs::get_path`] – Retrieve the exact value of an argument as a `Path`.
+/// - [`ParsedArgs::flag`] – Check if a flag was set.
+/// - [`ParsedArgs::positional`] – Get a slice of all positional arguments.
+/// - [`ParsedArgs::variadic`] – Get a slice of all variadic arguments.
+/// - [`ParsedArgs::variadic_os`] – Get the exact variadic arguments as `OsStr`s.
+/// - [`ParsedArgs::pos`] – Retrieve a positional argument by its index.
+/// - [`ParsedArgs::value_source`] – Where the value of an argument came from.
+/// - [`ParsedArgs::is_default`] – Check if the value of an argument is a default.
+/// - [`ParsedArgs::command_path`] – Names of the command these arguments belong to.
+/// - [`ParsedArgs::subcommand`] – The invoked subcommand and its parsed arguments.
+/// - [`ParsedArgs::parent`] – Parsed arguments of the parent command.
+/// - [`ParsedArgs::external_subcommand`] – The unknown subcommand and its raw arguments.
+#[derive(Debug, Clone, Default)]
+pub struct ParsedArgs {
+    /// Values for options (arguments that take a value)
+    values: HashMap<String, String>,
+    /// Flags (true if present, false otherwise)
+    flags: HashMap<String, bool>,
+    /// Positional arguments (ordered)
+    positional: Vec<String>,
+    /// Variadic arguments (remaining arguments after positional)
+    variadic: Vec<String>,
+    /// Where each present argument's value came from
+    sources: HashMap<String, ValueSource>,
+    /// Values split at their arg's `value_delimiter` (a single piece without one)
+    many: HashMap<String, Vec<String>>,
+    /// Pairs of `KEY=VALUE` args, in the order their keys first appear
+    maps: HashMap<String, Vec<(String, String)>>,
+    /// Make-style `NAME=value` arguments, in the order their names first appear
+    assignments: Vec<(String, String)>,
+    /// Names of the command these arguments belong to, starting with the app name
+    command_path: Vec<String>,
+    /// The invoked subcommand (name and its parsed arguments), if any
+    subcommand: Option<(String, Box<ParsedArgs>)>,
+    /// Parsed arguments of the parent command, if any
+    parent: Option<Box<ParsedArgs>>,
+    /// Name and raw arguments of an external subcommand, if one was invoked
+    external: Option<(String, Vec<OsString>)>,
+    /// Exact values of raw args that are not valid UTF-8
+    os_values: HashMap<String, OsString>,
+    /// Exact variadic values, when the variadic arg is raw and some are not valid UTF-8
+    os_variadic: Vec<OsString>,
+    /// Index in the command line of the tokens holding each arg's values, in
+    /// order, with the byte offset of the value in the token (`--name=value`)
+    positions: HashMap<String, Vec<(usize, usize)>>,
+    /// Index in the command line of each variadic value's token
+    variadic_positions: Vec<usize>,
+    /// Index in the command line of each assignment's token
+    assignment_positions: Vec<usize>,
+}
+
+impl ParsedArgs {
+    /// Retrieve the value of an option by name.
+    pub fn get(&self, name: &str) -> Option<&String> {
+        self.values.get(name)
+    }
+    
+    /// Retrieve the value of an arg set up with [`Arg::value_enum`] as its variant.
+    ///
+    /// ```ignore
+    /// let mode: Mode = parsed.get_enum("mode").unwrap_or(Mode::Fast);
+    /// ```
+    pub fn get_enum<T: ValueEnum>(&self, name: &str) -> Option<T> {
+        self.values.get(name).and_then(|value| T::from_value(value))
+    }
+    
+    /// Retrieve the pieces of a value split at its [`Arg::value_delimiter`].
+    ///
+    /// Every occurrence on the command line adds its pieces, and args without
+    /// a delimiter give each of their values as a piece.
+    ///
+    /// ```ignore
+    /// // --tags a,b --tags c
+    /// assert_eq!(parsed.get_many("tags").unwrap(), ["a", "b", "c"]);
+    /// ```
+    pub fn get_many(&self, name: &str) -> Option<&[String]> {
+        self.many.get(name).map(Vec::as_slice)
+    }
+    
+    /// Retrieve the pairs of a [`Arg::key_value`] arg, in the order their keys first appear.
+    ///
+    /// ```ignore
+    /// // -D name=luh -D debug=1
+    /// assert_eq!(parsed.get_map("define").unwrap()[0], ("name".into(), "luh".into()));
+    /// let defines: HashMap<_, _> = parsed.get_map("define").unwrap().iter().cloned().collect();
+    /// ```
+    pub fn get_map(&self, name: &str) -> Option<&[(String, String)]> {
+        self.maps.get(name).map(Vec::as_slice)
+    }
+    
+    /// The make-style `NAME=value` arguments, in the order their names first appear
+    /// (see [`Command::assignments`]).
+    pub fn assignments(&self) -> &[(String, String)] {
+        &self.assignments
+    }
+    
+    /// Retrieve the value of an assignment by name.
+    ///
+    /// ```ignore
+    /// // build CC=clang DEBUG=1 app
+    /// assert_eq!(parsed.assignment("CC"), Some("clang"));
+    /// ```
+    pub fn assignment(&self, name: &str) -> Option<&str> {
+        self.assignments.iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, value)| value.as_str())
+    }
+    
+    /// Retrieve the exact value of an argument, which may not be valid UTF-8 for
+    /// args marked with [`Arg::raw`].
+    pub fn get_os(&self, name: &str) -> Option<&OsStr> {
+        self.os_values.get(name)
+            .map(OsString::as_os_str)
+            .or_else(|| self.get(name).map(OsStr::new))
+    }
+    
+    /// Retrieve the exact value of an argument as a path, see [`ParsedArgs::get_os`].
+    pub fn get_path(&self, name: &str) -> Option<&Path> {
+        self.get_os(name).map(Path::new)
+    }
+    
+    /// Check if a flag was set.
+    pub fn flag(&self, name: &str) -> bool {
+        self.flags.get(name).copied().unwrap_or(false)
+    }
+    
+    /// Get a slice of all positional arguments, in index order.
+    ///
+    /// It ends at the first positional without a value, so an optional
+    /// positional after a missing one is only read by name.
+    pub fn positional(&self) -> &[String] {
+        &self.positional
+    }
+    
+    /// Get a slice of all variadic arguments.
+    pub fn variadic(&self) -> &[String] {
+        &self.variadic
+    }
+    
+    /// Get the exact variadic arguments, which may not be valid UTF-8 when the
+    /// variadic arg is marked with [`Arg::raw`].
+    pub fn variadic_os(&self) -> Vec<&OsStr> {
+        if self.os_variadic.is_empty() {
+            self.variadic.iter().map(OsStr::new).collect()
+        } else {
+            self.os_variadic.iter().map(OsString::as_os_str).collect()
+        }
+    }
+    
+    /// Retrieve a positional argument by its index, see [`ParsedArgs::positional`].
+    pub fn pos(&self, index: usize) -> Option<&String> {
+        self.positional.get(index)
+    }
+
+    /// Where the value of an argument came from, `None` if it has no value.
+    pub fn value_source(&self, name: &str) -> Option<ValueSource> {
+        self.sources.get(name).copied()
+    }
+    
+    /// Check if the value of an argument is a default rather than given by the user.
+    pub fn is_default(&self, name: &str) -> bool {
+        self.value_source(name) == Some(ValueSource::Default)
+    }
+
+    /// Names of the command these arguments belong to, starting with the app name
+    /// (e.g. `["moth", "config"]`). Lets one handler serve several commands.
+    pub fn command_path(&self) -> &[String] {
+        &self.command_path
+    }
+
+    /// The invoked subcommand and its parsed arguments, if any.
+    ///
+    /// Only set when walking down from a parent, e.g. from [`Matches::root_args`].
+    pub fn subcommand(&self) -> Option<(&str, &ParsedArgs)> {
+        self.subcommand.as_ref().map(|(name, args)| (name.as_str(), args.as_ref()))
+    }
+
+    /// Parsed arguments of the parent command, if any.
+    ///
+    /// Handlers use this to reach global options given before the subcommand name.
+    ///
+    /// ```ignore
+    /// let verbose = args.parent().is_some_and(|p| p.flag("verbose"));
+    /// ```
+    pub fn parent(&self) -> Option<&ParsedArgs> {
+        self.parent.as_deref()
+    }
+
+    /// The unknown subcommand name and the raw arguments after it.
+    ///
+    /// Set on the root arguments when [`CliApp::allow_external_subcommands`] or
+    /// [`CliApp::external_subcommands`] picked up a command the tree doesn't define.
+    /// The arguments are exactly as given, which may not be valid UTF-8 when
+    /// parsed with [`CliApp::try_parse_from_os`].
+    ///
+    /// ```ignore
+    /// // $ myapp -v deploy --fast
+    /// if let Some((name, args)) = parsed.external_subcommand() {
+    ///     assert_eq!((name, args), ("deploy", &[OsString::from("--fast")][..]));
+    /// }
+    /// ```
+    pub fn external_subcommand(&self) -> Option<(&str, &[OsString])> {
+        self.external.as_ref().map(|(name, args)| (name.as_str(), args.as_slice()))
+    }
+}
+
+/// Application state handed to handlers alongside their `ParsedArgs`.
+///
+/// `Context` is a typed map holding at most one value per type: database handles,
+/// loaded configuration, loggers and the like. It is usually filled once by the
+/// [`CliApp::init`] hook (after the global arguments are parsed) and then read by
+/// handlers registered with [`Command::context_handler`].
+///
+/// # Examples
+///
+/// ```ignore
+/// use luhcli::{Arg, CliApp, Command, Context, ParsedArgs};
+///
+/// struct Db { url: String }
+///
+/// let app = CliApp::new("myapp")
+///     .arg(Arg::new("db").takes_value().default_value("sqlite://app.db"))
+///     .init(|ctx: &mut Context, globals: &ParsedArgs| {
+///         ctx.insert(Db { url: globals.get("db").cloned().unwrap_or_default() });
+///         Ok(())
+///     })
+///     .subcommand(Command::new("migrate").context_handler(|ctx: &mut Context, _args: &ParsedArgs| {
+///         let db = ctx.state::<Db>()?;
+///         println!("migrating {}", db.url);
+///         Ok(())
+///     }));
+/// ```
+///
+/// # Provided Methods
+///
+/// - [`Context::new`] – Create an empty context.
+/// - [`Context::insert`] – Store a value, replacing any previous value of the same type.
+/// - [`Context::get`] – Borrow the value of a type.
+/// - [`Context::get_mut`] – Mutably borrow the value of a type.
+/// - [`Context::state`] – Borrow the value of a type, erroring when it is missing.
+/// - [`Context::state_mut`] – Mutably borrow the value of a type, erroring when it is missing.
+/// - [`Context::remove`] – Take the value of a type out of the context.
+/// - [`Context::contains`] – Check if a value of a type is stored.
+#[derive(Default)]
+pub struct Context {
+    values: HashMap<TypeId, Box<dyn Any>>,
+}
+
+impl Context {
+    /// Create an empty context.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Store a value, replacing (and returning) any previous value of the same type.
+    pub fn insert<T: 'static>(&mut self, value: T) -> Option<T> {
+        self.values.insert(TypeId::of::<T>(), Box::new(value))
+            .and_then(|old| old.downcast().ok())
+            .map(|old| *old)
+    }
+
+    /// Borrow the value of a type.
+    pub fn get<T: 'static>(&self) -> Option<&T> {
+        self.values.get(&TypeId::of::<T>()).and_then(|v| v.downcast_ref())
+    }
+
+    /// Mutably borrow the value of a type.
+    pub fn get_mut<T: 'static>(&mut self) -> Option<&mut T> {
+        self.values.get_mut(&TypeId::of::<T>()).and_then(|v| v.downcast_mut())
+    }
+
+    /// Borrow the value of a type, erroring when it is missing.
+    pub fn state<T: 'static>(&self) -> LuhTwin<&T> {
+        self.get().ok_or_else(|| at!("no '{}' in the context", type_name::<T>()).into())
+    }
+
+    /// Mutably borrow the value of a type, erroring when it is missing.
+    pub fn state_mut<T: 'static>(&mut self) -> LuhTwin<&mut T> {
+        self.get_mut().ok_or_else(|| at!("no '{}' in the context", type_name::<T>()).into())
+    }
+
+    /// Take the value of a type out of the context.
+    pub fn remove<T: 'static>(&mut self) -> Option<T> {
+        self.values.remove(&TypeId::of::<T>())
+            .and_then(|v| v.downcast().ok())
+            .map(|v| *v)
+    }
+
+    /// Check if a value of a type is stored.
+    pub fn contains<T: 'static>(&self) -> bool {
+        self.values.contains_key(&TypeId::of::<T>())
+    }
+}
+
+/// A boxed handler future resolving to the exit code of the run.
+#[cfg(feature = "async")]
+type HandlerFuture = std::pin::Pin<Box<dyn std::future::Future<Output = LuhTwin<i32>>>>;
+
+/// A boxed blocking handler returning the exit code of the run.
+type SyncHandler = Box<dyn Fn(&mut Context, &ParsedArgs) -> LuhTwin<i32>>;
+
+/// A command handler returning the exit code of the run.
+enum Handler {
+    /// Called directly, with the application context
+    Sync(SyncHandler),
+    /// Awaited by `CliApp::run_async`, with the application context moved in
+    #[cfg(feature = "async")]
+    Async(Box<dyn Fn(Context, ParsedArgs) -> HandlerFuture>),
+}
+
+/// A boxed hook run once per invocation to fill the `Context`.
+type InitHook = Box<dyn Fn(&mut Context, &ParsedArgs) -> LuhTwin<()>>;
+
+/// Represents a single CLI command in `luhcli`.
+///  
+/// Commands can have:
+/// - Arguments (`Arg`)
+/// - Subcommands (`Command`)
+/// - A handler function executed when the command is invoked.
+///
+/// # Examples
+///
+/// ```ignore
 /// use luhcli::{Command, Arg, ParsedArgs};
 /// use luhtwin::LuhTwin;
 ///
@@ -559,15 +1823,24 @@ impl ParsedArgs {
 /// - [`Command::usage`] – Set the usage string.
 /// - [`Command::arg`] – Add an argument to the command.
 /// - [`Command::subcommand`] – Add a subcommand.
+/// - [`Command::group`] – Add an argument group.
+/// - [`Command::assignments`] – Collect make-style `NAME=value` arguments.
+/// - [`Command::assignment_names`] – Restrict the names of assignments.
 /// - [`Command::handler`] – Set the handler function.
+/// - [`Command::context_handler`] – Set a handler function that also receives the `Context`.
+/// - [`Command::async_handler`] – Set an async handler function (`async` feature).
+/// - [`Command::async_context_handler`] – Set an async handler function that also receives the `Context` (`async` feature).
 /// - [`Command::print_help`] – Print the help output to the console.
 pub struct Command {
     name: String,
     about: String,
     usage: String,
     args: Vec<Arg>,
+    groups: Vec<ArgGroup>,
     subcommands: Vec<Command>,
-    handler: Option<Box<dyn Fn(&ParsedArgs) -> LuhTwin<()>>>,
+    handler: Option<Handler>,
+    assignments: bool,
+    assignment_names: Vec<String>,
 }
 
 impl Command {
@@ -578,8 +1851,11 @@ impl Command {
             about: String::new(),
             usage: String::new(),
             args: Vec::new(),
+            groups: Vec::new(),
             subcommands: Vec::new(),
             handler: None,
+            assignments: false,
+            assignment_names: Vec::new(),
         }
     }
     
@@ -588,32 +1864,256 @@ impl Command {
         self.about = about.into();
         self
     }
-    
-    /// Set the usage string.
-    pub fn usage(mut self, usage: impl Into<String>) -> Self {
-        self.usage = usage.into();
+    
+    /// Set the usage string.
+    pub fn usage(mut self, usage: impl Into<String>) -> Self {
+        self.usage = usage.into();
+        self
+    }
+    
+    /// Add an argument to the command.
+    pub fn arg(mut self, arg: Arg) -> Self {
+        self.args.push(arg);
+        self
+    }
+    
+    /// Add a subcommand.
+    pub fn subcommand(mut self, cmd: Command) -> Self {
+        self.subcommands.push(cmd);
+        self
+    }
+
+    /// Add an argument group, see [`ArgGroup`].
+    pub fn group(mut self, group: ArgGroup) -> Self {
+        self.groups.push(group);
+        self
+    }
+    
+    /// Collect make-style `NAME=value` arguments apart from the positionals.
+    ///
+    /// A token is an assignment when the part before its first `=` is an
+    /// identifier (letters, digits and `_`, not starting with a digit). They are
+    /// read with [`ParsedArgs::assignments`] and don't count as positionals, so
+    /// `build CC=clang DEBUG=1 app` has `app` at index 0. A name given twice
+    /// keeps its last value.
+    ///
+    /// ```ignore
+    /// Command::new("build")
+    ///     .assignments(true)
+    ///     .arg(Arg::positional("target", 0))
+    /// ```
+    pub fn assignments(mut self, enabled: bool) -> Self {
+        self.assignments = enabled;
+        self
+    }
+    
+    /// Only accept assignments to the given names, others are an error.
+    pub fn assignment_names<I, S>(mut self, names: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.assignment_names = names.into_iter().map(Into::into).collect();
+        self
+    }
+    
+    /// The name and value of a `NAME=value` token, when this command collects assignments.
+    fn assignment<'a>(&self, token: &'a str) -> Option<(&'a str, &'a str)> {
+        if !self.assignments {
+            return None;
+        }
+        
+        token.split_once('=').filter(|(name, _)| is_identifier(name))
+    }
+    
+    /// Set the handler function.
+    ///
+    /// The handler may return `()` (exit code `EXIT_SUCCESS`) or an integer
+    /// exit code of its own choosing, see [`IntoExitCode`].
+    pub fn handler<F, R>(mut self, f: F) -> Self 
+    where 
+        F: Fn(&ParsedArgs) -> LuhTwin<R> + 'static,
+        R: IntoExitCode,
+    {
+        self.handler = Some(Handler::Sync(Box::new(move |_, parsed| {
+            f(parsed).map(IntoExitCode::into_exit_code)
+        })));
+        self
+    }
+
+    /// Set a handler function that also receives the application [`Context`].
+    ///
+    /// ```ignore
+    /// Command::new("migrate").context_handler(|ctx: &mut Context, args: &ParsedArgs| {
+    ///     let db = ctx.state_mut::<Db>()?;
+    ///     db.migrate(args.flag("dry-run"))
+    /// });
+    /// ```
+    pub fn context_handler<F, R>(mut self, f: F) -> Self
+    where
+        F: Fn(&mut Context, &ParsedArgs) -> LuhTwin<R> + 'static,
+        R: IntoExitCode,
+    {
+        self.handler = Some(Handler::Sync(Box::new(move |ctx, parsed| {
+            f(ctx, parsed).map(IntoExitCode::into_exit_code)
+        })));
+        self
+    }
+
+    /// Set an async handler function, awaited by [`CliApp::run_async`].
+    ///
+    /// The handler receives its own copy of the parsed arguments so the future
+    /// can hold on to them. Running a command with an async handler through the
+    /// blocking [`CliApp::run`] is an error.
+    ///
+    /// ```ignore
+    /// Command::new("fetch").async_handler(|args: ParsedArgs| async move {
+    ///     let body = http_get(args.get("url").unwrap()).await?;
+    ///     println!("{}", body);
+    ///     Ok(())
+    /// });
+    /// ```
+    #[cfg(feature = "async")]
+    pub fn async_handler<F, Fut, R>(mut self, f: F) -> Self
+    where
+        F: Fn(ParsedArgs) -> Fut + 'static,
+        Fut: std::future::Future<Output = LuhTwin<R>> + 'static,
+        R: IntoExitCode,
+    {
+        self.handler = Some(Handler::Async(Box::new(move |_, parsed| {
+            let fut = f(parsed);
+            Box::pin(async move { fut.await.map(IntoExitCode::into_exit_code) })
+        })));
+        self
+    }
+
+    /// Set an async handler function that also receives the application [`Context`].
+    ///
+    /// The context is moved into the handler so the future can hold on to it,
+    /// share state that has to outlive the run through an `Rc` or `Arc` in it.
+    ///
+    /// ```ignore
+    /// Command::new("sync").async_context_handler(|ctx: Context, args: ParsedArgs| async move {
+    ///     let db = ctx.state::<Db>()?;
+    ///     db.sync(args.flag("full")).await
+    /// });
+    /// ```
+    #[cfg(feature = "async")]
+    pub fn async_context_handler<F, Fut, R>(mut self, f: F) -> Self
+    where
+        F: Fn(Context, ParsedArgs) -> Fut + 'static,
+        Fut: std::future::Future<Output = LuhTwin<R>> + 'static,
+        R: IntoExitCode,
+    {
+        self.handler = Some(Handler::Async(Box::new(move |ctx, parsed| {
+            let fut = f(ctx, parsed);
+            Box::pin(async move { fut.await.map(IntoExitCode::into_exit_code) })
+        })));
         self
     }
     
-    /// Add an argument to the command.
-    pub fn arg(mut self, arg: Arg) -> Self {
-        self.args.push(arg);
-        self
+    /// Find the first token naming one of our subcommands, skipping over our own
+    /// flags and options (and their values) on the way.
+    fn find_subcommand(&self, args: &[String]) -> Option<(usize, &Command)> {
+        if self.subcommands.is_empty() {
+            return None;
+        }
+
+        let i = self.command_word(args)?;
+        self.subcommands.iter()
+            .find(|s| s.name == args[i])
+            .map(|sub| (i, sub))
+    }
+
+    /// Index of the first token that is neither one of this command's flags nor an option value.
+    fn command_word(&self, args: &[String]) -> Option<usize> {
+        let mut i = 0;
+        while i < args.len() {
+            let arg = &args[i];
+
+            if arg.starts_with('-') && arg.len() > 1 {
+                let arg_def = if let Some(long) = arg.strip_prefix("--") {
+                    if long.contains('=') {
+                        None
+                    } else {
+                        self.args.iter().find(|a| a.long.as_deref() == Some(long))
+                    }
+                } else {
+                    let c = arg.chars().nth(1).unwrap();
+                    self.args.iter().find(|a| a.short == Some(c))
+                };
+
+                if arg_def.is_some_and(|a| a.arg_type == ArgType::Option) {
+                    i += 1;
+                }
+            } else if self.assignment(arg).is_none() {
+                return Some(i);
+            }
+
+            i += 1;
+        }
+
+        None
     }
     
-    /// Add a subcommand.
-    pub fn subcommand(mut self, cmd: Command) -> Self {
-        self.subcommands.push(cmd);
-        self
+    /// Whether the environment or the config section holds values for any of our args.
+    fn has_layered_values(&self, config: Option<&ConfigSection>) -> bool {
+        config.is_some_and(|c| !c.values.is_empty())
+            || self.args.iter().any(|a| a.env.as_ref().is_some_and(|var| std::env::var_os(var).is_some()))
     }
     
-    /// Set the handler function.
-    pub fn handler<F>(mut self, f: F) -> Self 
-    where 
-        F: Fn(&ParsedArgs) -> LuhTwin<()> + 'static 
-    {
-        self.handler = Some(Box::new(f));
-        self
+    /// Collect every definition mistake in this command and its subcommands.
+    fn check_definitions(&self, path: &str, problems: &mut Vec<String>) {
+        let mut known = Vec::new();
+        collect_arg_names(&self.args, &mut known);
+        let arg_names = known.clone();
+        known.extend(self.groups.iter().map(|g| g.name.clone()));
+
+        check_arg_scope(path, &[], &self.args, problems);
+        
+        for (i, group) in self.groups.iter().enumerate() {
+            if self.groups[..i].iter().any(|g| g.name == group.name) {
+                problems.push(format!("{}: group '{}' is defined more than once", path, group.name));
+            }
+            
+            if arg_names.contains(&group.name) {
+                problems.push(format!("{}: group '{}' has the same name as an arg", path, group.name));
+            }
+            
+            if group.args.is_empty() {
+                problems.push(format!("{}: group '{}' has no args", path, group.name));
+            }
+            
+            for member in &group.args {
+                if !arg_names.contains(member) {
+                    problems.push(format!("{}: group '{}' contains unknown arg '{}'", path, group.name, member));
+                }
+            }
+            
+            for name in group.conflicts_with.iter().chain(&group.requires) {
+                if !known.contains(name) {
+                    problems.push(format!("{}: group '{}' refers to unknown arg or group '{}'", path, group.name, name));
+                }
+            }
+        }
+        for arg in &self.args {
+            check_arg(path, &self.args, arg, &known, problems);
+        }
+        
+        if !self.assignment_names.is_empty() && !self.assignments {
+            problems.push(format!("{}: assignment names are set but assignments are not enabled", path));
+        }
+        
+        for name in self.assignment_names.iter().filter(|n| !is_identifier(n)) {
+            problems.push(format!("{}: assignment name '{}' is not an identifier", path, name));
+        }
+
+        for (i, sub) in self.subcommands.iter().enumerate() {
+            if self.subcommands[..i].iter().any(|s| s.name == sub.name) {
+                problems.push(format!("{}: subcommand '{}' is defined more than once", path, sub.name));
+            }
+            sub.check_definitions(&format!("{} {}", path, sub.name), problems);
+        }
     }
     
     fn get_active_args(&self, parsed_positionals: &[String]) -> Vec<Arg> {
@@ -623,7 +2123,7 @@ impl Command {
             if let ArgType::Positional { index } = arg.arg_type {
                 if let Some(value) = parsed_positionals.get(index) {
                     for chain in &arg.children {
-                        if &chain.when_value == value {
+                        if arg.is_when_value(value, &chain.when_value) {
                             for child in &chain.args {
                                 let mut child_clone = child.clone();
                                 if let ArgType::Positional { index: child_idx } = child_clone.arg_type {
@@ -642,20 +2142,37 @@ impl Command {
         active_args
     }
     
-    fn parse(&self, args: &[String]) -> LuhTwin<ParsedArgs> {
+    /// Parse the args of this command, `first` being the index of `args[0]` in
+    /// the whole command line. On errors about a token, `blame` holds its index.
+    fn parse(
+        &self,
+        args: &[String],
+        first: usize,
+        config: Option<&ConfigSection>,
+        blame: &mut Option<usize>,
+    ) -> LuhTwin<ParsedArgs> {
+        *blame = None;
         let mut values = HashMap::new();
         let mut flags = HashMap::new();
-        let mut positional = Vec::new();
         let mut variadic = Vec::new();
         let mut seen_args = Vec::new();
+        let mut sources = HashMap::new();
+        let mut occurrences: HashMap<String, Vec<String>> = HashMap::new();
+        let mut assignments: Vec<(String, String)> = Vec::new();
         let mut i = 0;
         
+        // token indices, relative to `args`, of option values (with the byte
+        // offset of the value in its token), positionals and assignments
+        let mut occurrences_at: HashMap<String, Vec<(usize, usize)>> = HashMap::new();
+        let mut positional_at = Vec::new();
+        let mut assignment_at = Vec::new();
+        
         let mut positional_raw = Vec::new();
         
         let mut temp_i = 0;
         while temp_i < args.len() {
             let arg = &args[temp_i];
-            if !arg.starts_with('-') {
+            if !arg.starts_with('-') && self.assignment(arg).is_none() {
                 positional_raw.push(arg.clone());
             }
             temp_i += 1;
@@ -667,6 +2184,7 @@ impl Command {
         
         while i < args.len() {
             let arg = &args[i];
+            *blame = Some(first + i);
             
             if arg.starts_with("--") {
                 let key = arg.trim_start_matches("--");
@@ -674,6 +2192,8 @@ impl Command {
                 if let Some((k, v)) = key.split_once('=') {
                     if let Some(arg_def) = active_args.iter().find(|a| a.long.as_deref() == Some(k)) {
                         values.insert(arg_def.name.clone(), v.to_string());
+                        occurrences.entry(arg_def.name.clone()).or_default().push(v.to_string());
+                        occurrences_at.entry(arg_def.name.clone()).or_default().push((i, arg.len() - v.len()));
                         seen_args.push(arg_def.name.clone());
                     } else {
                         return Err(at!("unknown option: --{}", k).into());
@@ -681,9 +2201,10 @@ impl Command {
                 } else if let Some(arg_def) = active_args.iter().find(|a| a.long.as_deref() == Some(key)) {
                     match arg_def.arg_type {
                         ArgType::Option => {
-                            i += 1;
-                            if i < args.len() {
-                                values.insert(arg_def.name.clone(), args[i].clone());
+                            if let Some(value) = option_value(arg_def, args, &mut i) {
+                                occurrences.entry(arg_def.name.clone()).or_default().push(value.clone());
+                                occurrences_at.entry(arg_def.name.clone()).or_default().push((i, 0));
+                                values.insert(arg_def.name.clone(), value);
                                 seen_args.push(arg_def.name.clone());
                             } else {
                                 return Err(at!("--{} requires a value", key).into());
@@ -704,9 +2225,10 @@ impl Command {
                 if let Some(arg_def) = active_args.iter().find(|a| a.short == Some(c)) {
                     match arg_def.arg_type {
                         ArgType::Option => {
-                            i += 1;
-                            if i < args.len() {
-                                values.insert(arg_def.name.clone(), args[i].clone());
+                            if let Some(value) = option_value(arg_def, args, &mut i) {
+                                occurrences.entry(arg_def.name.clone()).or_default().push(value.clone());
+                                occurrences_at.entry(arg_def.name.clone()).or_default().push((i, 0));
+                                values.insert(arg_def.name.clone(), value);
                                 seen_args.push(arg_def.name.clone());
                             } else {
                                 return Err(at!("-{} requires a value", c).into());
@@ -721,13 +2243,74 @@ impl Command {
                 } else {
                     return Err(at!("unknown option: -{}", c).into());
                 }
+            } else if let Some((name, value)) = self.assignment(arg) {
+                if !self.assignment_names.is_empty() && !self.assignment_names.iter().any(|n| n == name) {
+                    return Err(at!(
+                        "unknown assignment: {}. allowed names: {}",
+                        name,
+                        self.assignment_names.join(", ")
+                    ).into());
+                }
+                
+                assignment_at.push(first + i);
+                match assignments.iter_mut().find(|(n, _)| n == name) {
+                    Some(existing) => existing.1 = value.to_string(),
+                    None => assignments.push((name.to_string(), value.to_string())),
+                }
             } else {
                 positional_raw.push(arg.clone());
+                positional_at.push(i);
             }
             
             i += 1;
         }
         
+        // checked before anything looks at them, so requirements see canonical values
+        for arg_def in active_args.iter().filter(|a| a.arg_type == ArgType::Option) {
+            let Some(given) = occurrences.get_mut(&arg_def.name) else {
+                continue;
+            };
+            for (value, (at, _)) in given.iter_mut().zip(&occurrences_at[&arg_def.name]) {
+                *blame = Some(first + at);
+                *value = arg_def.check_value(value, None)?;
+            }
+            if let Some(last) = given.last() {
+                values.insert(arg_def.name.clone(), last.clone());
+            }
+        }
+        
+        *blame = None;
+        for arg_def in &active_args {
+            if !matches!(arg_def.arg_type, ArgType::Flag | ArgType::Option) || seen_args.contains(&arg_def.name) {
+                continue;
+            }
+            
+            if let Some((layered, source, origin)) = arg_def.layered_value(config) {
+                let value = if arg_def.key_value.is_some() {
+                    // every element of a config array is a pair of its own
+                    let layered = layered.iter()
+                        .map(|v| arg_def.check_value(v, Some(&origin)))
+                        .collect::<LuhTwin<Vec<_>>>()?;
+                    occurrences.insert(arg_def.name.clone(), layered.clone());
+                    layered.last().cloned()
+                } else {
+                    arg_def.single_value(&layered)
+                };
+                let Some(value) = value else {
+                    return Err(at!("'{}' from {} expects a single value", arg_def.name, origin).into());
+                };
+                
+                if arg_def.arg_type == ArgType::Flag {
+                    flags.insert(arg_def.name.clone(), is_truthy(&value));
+                } else {
+                    let value = arg_def.check_value(&value, Some(&origin))?;
+                    values.insert(arg_def.name.clone(), value);
+                }
+                seen_args.push(arg_def.name.clone());
+                sources.insert(arg_def.name.clone(), source);
+            }
+        }
+        
         let positional_defs: Vec<_> = active_args.iter()
             .filter(|a| matches!(a.arg_type, ArgType::Positional { .. }))
             .collect();
@@ -747,26 +2330,25 @@ impl Command {
         for arg_def in positional_sorted.iter() {
             if let ArgType::Positional { index } = arg_def.arg_type {
                 if let Some(value) = positional_raw.get(index) {
-                    // Validate possible values
-                    if !arg_def.possible_values.is_empty() && !arg_def.possible_values.contains(value) {
-                        return Err(at!(
-                            "invalid value '{}' for '{}'. possible values: {}",
-                            value,
-                            arg_def.name,
-                            arg_def.possible_values.join(", ")
-                        ).into());
-                    }
+                    *blame = Some(first + positional_at[index]);
+                    let value = arg_def.check_value(value, None)?;
+                    *blame = None;
                     
-                    positional.push(value.clone());
-                    values.insert(arg_def.name.clone(), value.clone());
+                    values.insert(arg_def.name.clone(), value);
                     seen_args.push(arg_def.name.clone());
-                } else if arg_def.required {
-                    if let Some(default) = &arg_def.default_value {
-                        positional.push(default.clone());
-                        values.insert(arg_def.name.clone(), default.clone());
-                    } else {
-                        return Err(at!("missing required positional argument: {}", arg_def.name).into());
-                    }
+                } else if let Some((layered, source, origin)) = arg_def.layered_value(config) {
+                    let Some(value) = arg_def.single_value(&layered) else {
+                        return Err(at!("'{}' from {} expects a single value", arg_def.name, origin).into());
+                    };
+                    let value = arg_def.check_value(&value, Some(&origin))?;
+                    
+                    values.insert(arg_def.name.clone(), value);
+                    seen_args.push(arg_def.name.clone());
+                    sources.insert(arg_def.name.clone(), source);
+                } else if let Some(default) = arg_def.default_for(&values) {
+                    let default = arg_def.check_value(default, Some(DEFAULT_ORIGIN))?;
+                    values.insert(arg_def.name.clone(), default);
+                    sources.insert(arg_def.name.clone(), ValueSource::Default);
                 }
             }
         }
@@ -776,14 +2358,71 @@ impl Command {
             variadic = positional_raw.get(start_index..).unwrap_or(&[]).to_vec();
             
             if !variadic.is_empty() {
+                variadic = variadic.iter()
+                    .zip(&positional_at[start_index..])
+                    .map(|(v, at)| {
+                        *blame = Some(first + at);
+                        variadic_def.check_value(v, None)
+                    })
+                    .collect::<LuhTwin<_>>()?;
+                *blame = None;
                 seen_args.push(variadic_def.name.clone());
+            } else if let Some((layered, source, origin)) = variadic_def.layered_value(config) {
+                variadic = layered.iter()
+                    .map(|v| variadic_def.check_value(v, Some(&origin)))
+                    .collect::<LuhTwin<_>>()?;
+                seen_args.push(variadic_def.name.clone());
+                sources.insert(variadic_def.name.clone(), source);
+            }
+        }
+        
+        for name in &seen_args {
+            sources.entry(name.clone()).or_insert(ValueSource::CommandLine);
+        }
+        
+        // every default goes in before any requirement is checked, plain ones
+        // first so conditional defaults can see them
+        let mut missing: Vec<_> = active_args.iter()
+            .filter(|a| !seen_args.contains(&a.name) && !sources.contains_key(&a.name))
+            .collect();
+        missing.sort_by_key(|a| !a.default_value_if.is_empty());
+        
+        for arg_def in &missing {
+            let Some(default) = arg_def.default_for(&values) else {
+                continue;
+            };
+            match arg_def.arg_type {
+                ArgType::Flag => {
+                    flags.insert(arg_def.name.clone(), is_truthy(default));
+                }
+                ArgType::Variadic => {
+                    variadic.push(arg_def.check_value(default, Some(DEFAULT_ORIGIN))?);
+                }
+                _ => {
+                    let default = arg_def.check_value(default, Some(DEFAULT_ORIGIN))?;
+                    values.insert(arg_def.name.clone(), default);
+                }
             }
+            sources.insert(arg_def.name.clone(), ValueSource::Default);
         }
         
+        let present_groups: Vec<_> = self.groups.iter()
+            .filter(|g| g.args.iter().any(|a| seen_args.contains(a)))
+            .collect();
+        seen_args.extend(present_groups.iter().map(|g| g.name.clone()));
+        
+        // conditions on values see defaults, and a default satisfies whatever
+        // requires its arg; being given means on the command line, in the
+        // environment or the config file
+        let provided = |name: &String| {
+            seen_args.contains(name)
+                || (sources.get(name) == Some(&ValueSource::Default) && flags.get(name) != Some(&false))
+        };
+        
         for arg_def in &active_args {
             if seen_args.contains(&arg_def.name) {
                 for dep in &arg_def.depends_on {
-                    if !seen_args.contains(dep) {
+                    if !provided(dep) {
                         return Err(at!(
                             "'{}' requires '{}' to be specified",
                             arg_def.name,
@@ -801,32 +2440,172 @@ impl Command {
                         ).into());
                     }
                 }
+                
+                for (dep, expected) in &arg_def.depends_on_value {
+                    match values.get(dep) {
+                        Some(value) if value == expected => {}
+                        Some(value) => return Err(at!(
+                            "'{}' requires '{}' to be '{}' (got '{}')",
+                            arg_def.name,
+                            dep,
+                            expected,
+                            value
+                        ).into()),
+                        None => return Err(at!(
+                            "'{}' requires '{}' to be '{}' (not specified)",
+                            arg_def.name,
+                            dep,
+                            expected
+                        ).into()),
+                    }
+                }
+            }
+            
+            for (value, req) in &arg_def.requires_if {
+                if values.get(&arg_def.name) == Some(value) && !provided(req) {
+                    return Err(at!(
+                        "'{}' is required when '{}' is '{}'",
+                        req,
+                        arg_def.name,
+                        value
+                    ).into());
+                }
             }
         }
         
-        for arg_def in &active_args {
-            if arg_def.required && !seen_args.contains(&arg_def.name) {
-                if let Some(default) = &arg_def.default_value {
+        for group in &self.groups {
+            let given: Vec<_> = group.args.iter()
+                .filter(|a| seen_args.contains(a))
+                .collect();
+            
+            if given.is_empty() {
+                if group.required {
+                    return Err(at!(
+                        "group '{}' requires {}",
+                        group.name,
+                        group.describe(&active_args)
+                    ).into());
+                }
+                continue;
+            }
+            
+            if !group.multiple && given.len() > 1 {
+                return Err(at!(
+                    "{} and {} cannot be used together (group '{}' allows {})",
+                    display_name(&active_args, given[0]),
+                    display_name(&active_args, given[1]),
+                    group.name,
+                    group.describe(&active_args)
+                ).into());
+            }
+            
+            for conflict in &group.conflicts_with {
+                if seen_args.contains(conflict) {
+                    return Err(at!(
+                        "group '{}' ({}) conflicts with '{}'",
+                        group.name,
+                        display_name(&active_args, given[0]),
+                        conflict
+                    ).into());
+                }
+            }
+            
+            for req in &group.requires {
+                if !provided(req) {
+                    return Err(at!(
+                        "group '{}' ({}) requires '{}' to be specified",
+                        group.name,
+                        display_name(&active_args, given[0]),
+                        req
+                    ).into());
+                }
+            }
+        }
+        
+        missing.retain(|a| !sources.contains_key(&a.name));
+        for arg_def in missing {
+            if let Some(reason) = missing_requirement(arg_def, &seen_args, &values) {
+                #[cfg(feature = "prompt")]
+                if let Some(answer) = prompt::ask(arg_def)? {
                     match arg_def.arg_type {
-                        ArgType::Option => {
-                            values.insert(arg_def.name.clone(), default.clone());
-                        }
-                        ArgType::Flag => {
-                            flags.insert(arg_def.name.clone(), true);
+                        ArgType::Variadic => variadic = answer,
+                        _ => {
+                            values.insert(arg_def.name.clone(), answer[0].clone());
                         }
-                        _ => {}
                     }
-                } else {
-                    return Err(at!("required argument '{}' not provided", arg_def.name).into());
+                    sources.insert(arg_def.name.clone(), ValueSource::Prompt);
+                    continue;
+                }
+                
+                return Err(at!(reason).into());
+            }
+        }
+        
+        sources.retain(|name, _| active_args.iter().any(|a| &a.name == name));
+        
+        // `positional[i]` is always the value at index `i`, so it stops at the
+        // first positional without one
+        let positional: Vec<_> = positional_sorted.iter()
+            .map_while(|a| values.get(&a.name).cloned())
+            .collect();
+        
+        let mut many = HashMap::new();
+        let mut maps = HashMap::new();
+        for (name, value) in &values {
+            let Some(arg_def) = active_args.iter().find(|a| &a.name == name) else {
+                continue;
+            };
+            
+            // defaults and prompt answers are a single occurrence
+            let given = occurrences.get(name).cloned().unwrap_or_else(|| vec![value.clone()]);
+            if let Some(separator) = arg_def.key_value {
+                maps.insert(name.clone(), arg_def.key_values(separator, &given)?);
+            }
+            many.insert(name.clone(), given.iter().flat_map(|v| arg_def.split_value(v)).collect());
+        }
+        
+        if let Some(variadic_def) = variadic_def.filter(|a| a.value_delimiter.is_some()) {
+            variadic = variadic.iter().flat_map(|v| variadic_def.split_value(v)).collect();
+        }
+        
+        let mut positions: HashMap<_, Vec<_>> = occurrences_at.into_iter()
+            .map(|(name, at)| (name, at.into_iter().map(|(at, offset)| (first + at, offset)).collect()))
+            .collect();
+        for arg_def in &positional_sorted {
+            if let ArgType::Positional { index } = arg_def.arg_type {
+                if let Some(at) = positional_at.get(index) {
+                    positions.insert(arg_def.name.clone(), vec![(first + at, 0)]);
                 }
             }
         }
+        let variadic_positions = match variadic_def {
+            Some(_) => positional_at.iter().skip(positional_sorted.len()).map(|at| first + at).collect(),
+            None => Vec::new(),
+        };
         
-        Ok(ParsedArgs { values, flags, positional, variadic })
+        Ok(ParsedArgs {
+            values,
+            flags,
+            positional,
+            variadic,
+            sources,
+            many,
+            maps,
+            assignments,
+            positions,
+            variadic_positions,
+            assignment_positions: assignment_at,
+            ..Default::default()
+        })
     }
     
-    /// Set the handler function.
+    /// Print the help of the command, `full_path` naming it in the header.
     pub fn print_help(&self, full_path: &str) {
+        println!("{}", self.help_text(full_path));
+    }
+    
+    /// The help printed by `print_help`.
+    fn help_text(&self, full_path: &str) -> String {
         use std::fmt::Write as _;
 
         let mut out = String::new();
@@ -843,32 +2622,54 @@ impl Command {
         if !positional_args.is_empty() {
             writeln!(out, "\narguments:").unwrap();
             for arg in positional_args {
-                let mut arg_str = format!("  <{}>", arg.name);
+                let mut arg_str = format!("  <{}>", arg.placeholder());
+                if let Some(delimiter) = arg.value_delimiter {
+                    arg_str.push_str(&format!("{}...", delimiter));
+                }
                 if matches!(arg.arg_type, ArgType::Variadic) {
-                    arg_str = format!("  <{}>...", arg.name);
+                    arg_str = format!("  <{}>...", arg.placeholder());
                 }
                 
-                if !arg.possible_values.is_empty() {
-                    arg_str.push_str(&format!(" [{}]", arg.possible_values.join("|")));
-                }
+                arg_str.push_str(&arg.value_list());
                 
                 writeln!(out, "{:<40} {}", arg_str, arg.help).unwrap();
                 
+                for line in possible_value_lines(arg) {
+                    writeln!(out, "    {}", line).unwrap();
+                }
+                
                 if !arg.depends_on.is_empty() {
                     writeln!(out, "    depends on: {}", arg.depends_on.join(", ")).unwrap();
                 }
                 
+                for line in requirement_lines(arg) {
+                    writeln!(out, "    {}", line).unwrap();
+                }
+                
+                if let Some(var) = &arg.env {
+                    writeln!(out, "    env: {}", var).unwrap();
+                }
+                
+                if let Some(default) = &arg.default_value {
+                    writeln!(out, "    default: {}", default).unwrap();
+                }
+                
+                for (other, value, default) in &arg.default_value_if {
+                    writeln!(out, "    default: {} when {} = '{}'", default, other, value).unwrap();
+                }
+                
                 if !arg.children.is_empty() {
                     writeln!(out).unwrap();
                     for chain in &arg.children {
                         writeln!(out, "  when {} = '{}':", arg.name, chain.when_value).unwrap();
                         
                         for child in &chain.args {
-                            let child_str = match child.arg_type {
-                                ArgType::Positional { .. } => format!("    <{}>", child.name),
-                                ArgType::Variadic => format!("    <{}>...", child.name),
+                            let mut child_str = match child.arg_type {
+                                ArgType::Positional { .. } => format!("    <{}>", child.placeholder()),
+                                ArgType::Variadic => format!("    <{}>...", child.placeholder()),
                                 _ => continue,
                             };
+                            child_str.push_str(&child.value_list());
                             writeln!(out, "    {:<36} {}", child_str, child.help).unwrap();
                         }
                         
@@ -893,8 +2694,11 @@ impl Command {
                                     opt_str.push_str(&format!("--{}", l));
                                 }
                                 
-                                if matches!(child.arg_type, ArgType::Option) {
-                                    opt_str.push_str(&format!(" <{}>", child.name));
+                                if let Some(separator) = child.key_value {
+                                    opt_str.push_str(&format!(" <key{}value>...", separator));
+                                } else if matches!(child.arg_type, ArgType::Option) {
+                                    opt_str.push_str(&format!(" <{}>", child.placeholder()));
+                                    opt_str.push_str(&child.value_list());
                                 }
                                 
                                 writeln!(out, "    {:<36} {}", opt_str, child.help).unwrap();
@@ -927,12 +2731,23 @@ impl Command {
                     opt_str.push_str(&format!("--{}", l));
                 }
                 
-                if matches!(arg.arg_type, ArgType::Option) {
-                    opt_str.push_str(&format!(" <{}>", arg.name));
+                if let Some(separator) = arg.key_value {
+                    opt_str.push_str(&format!(" <key{}value>...", separator));
+                } else if matches!(arg.arg_type, ArgType::Option) {
+                    opt_str.push_str(&format!(" <{}>", arg.placeholder()));
+                    if let Some(delimiter) = arg.value_delimiter {
+                        opt_str.push_str(&format!("{}...", delimiter));
+                    }
+                    
+                    opt_str.push_str(&arg.value_list());
                 }
                 
                 writeln!(out, "{:<30} {}", opt_str, arg.help).unwrap();
                 
+                for line in possible_value_lines(arg) {
+                    writeln!(out, "{:<30}   {}", "", line).unwrap();
+                }
+                
                 if !arg.depends_on.is_empty() {
                     writeln!(out, "{:<30}   depends on: {}", "", arg.depends_on.join(", ")).unwrap();
                 }
@@ -940,25 +2755,404 @@ impl Command {
                 if !arg.conflicts_with.is_empty() {
                     writeln!(out, "{:<30}   conflicts with: {}", "", arg.conflicts_with.join(", ")).unwrap();
                 }
-                
-                if let Some(default) = &arg.default_value {
-                    writeln!(out, "{:<30}   default: {}", "", default).unwrap();
+                
+                if !arg.allowed_keys.is_empty() {
+                    writeln!(out, "{:<30}   allowed keys: {}", "", arg.allowed_keys.join(", ")).unwrap();
+                }
+                
+                for line in requirement_lines(arg) {
+                    writeln!(out, "{:<30}   {}", "", line).unwrap();
+                }
+                
+                if let Some(var) = &arg.env {
+                    writeln!(out, "{:<30}   env: {}", "", var).unwrap();
+                }
+                
+                if let Some(default) = &arg.default_value {
+                    writeln!(out, "{:<30}   default: {}", "", default).unwrap();
+                }
+                
+                for (other, value, default) in &arg.default_value_if {
+                    writeln!(out, "{:<30}   default: {} when {} = '{}'", "", default, other, value).unwrap();
+                }
+                
+                if let Some(missing) = &arg.default_missing_value {
+                    writeln!(out, "{:<30}   default without a value: {}", "", missing).unwrap();
+                }
+            }
+        }
+        
+        if self.assignments {
+            writeln!(out, "\nassignments:").unwrap();
+            if self.assignment_names.is_empty() {
+                writeln!(out, "  NAME=<value>").unwrap();
+            }
+            for name in &self.assignment_names {
+                writeln!(out, "  {}=<value>", name).unwrap();
+            }
+        }
+        
+        if !self.groups.is_empty() {
+            writeln!(out, "\ngroups:").unwrap();
+            for group in &self.groups {
+                writeln!(out, "{:<30} {}", format!("  {}", group.name), group.help).unwrap();
+                writeln!(out, "{:<30}   {}", "", group.describe(&self.args)).unwrap();
+                
+                if !group.requires.is_empty() {
+                    writeln!(out, "{:<30}   requires: {}", "", group.requires.join(", ")).unwrap();
+                }
+                
+                if !group.conflicts_with.is_empty() {
+                    writeln!(out, "{:<30}   conflicts with: {}", "", group.conflicts_with.join(", ")).unwrap();
+                }
+            }
+        }
+        
+        if !self.subcommands.is_empty() {
+            writeln!(out, "\ncommands:").unwrap();
+            for sub in &self.subcommands {
+                writeln!(out, "  {:<20} {}", sub.name, sub.about).unwrap();
+            }
+        }
+
+        while out.ends_with('\n') || out.ends_with(' ') {
+            out.pop();
+        }
+
+        out
+    }
+}
+
+/// Take the value of an option at `args[*i]`, advancing `i` past it.
+///
+/// Falls back to the option's `default_missing_value` when there is no next
+/// argument or the next argument is another flag.
+fn option_value(arg_def: &Arg, args: &[String], i: &mut usize) -> Option<String> {
+    match (args.get(*i + 1), &arg_def.default_missing_value) {
+        (Some(next), Some(missing)) if next.starts_with('-') && next.len() > 1 => Some(missing.clone()),
+        (Some(next), _) => {
+            *i += 1;
+            Some(next.clone())
+        }
+        (None, missing) => missing.clone(),
+    }
+}
+
+/// Whether `name` can be assigned to with `NAME=value` (letters, digits and `_`, not starting with a digit).
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Bounds of a range in words, e.g. `between 1 and 65535` or `at most 64`.
+fn describe_bounds<T: Display>(bounds: &(Bound<T>, Bound<T>)) -> String {
+    let lower = match &bounds.0 {
+        Bound::Included(n) => Some(format!("at least {}", n)),
+        Bound::Excluded(n) => Some(format!("greater than {}", n)),
+        Bound::Unbounded => None,
+    };
+    let upper = match &bounds.1 {
+        Bound::Included(n) => Some(format!("at most {}", n)),
+        Bound::Excluded(n) => Some(format!("less than {}", n)),
+        Bound::Unbounded => None,
+    };
+    
+    match (&bounds.0, &bounds.1, lower, upper) {
+        (Bound::Included(a), Bound::Included(b), _, _) => format!("between {} and {}", a, b),
+        (_, _, Some(lower), Some(upper)) => format!("{} and {}", lower, upper),
+        (_, _, Some(bound), None) | (_, _, None, Some(bound)) => bound,
+        (_, _, None, None) => "of any size".to_string(),
+    }
+}
+
+/// Where a default value comes from, in errors about it.
+const DEFAULT_ORIGIN: &str = "the default value";
+
+/// Whether a default value turns a flag on.
+fn is_truthy(value: &str) -> bool {
+    !matches!(value, "" | "0" | "false" | "no" | "off")
+}
+
+/// Why a missing arg has to be given, or `None` when it may be left out.
+fn missing_requirement(arg_def: &Arg, seen_args: &[String], values: &HashMap<String, String>) -> Option<String> {
+    for (other, value) in &arg_def.required_if {
+        if values.get(other) == Some(value) {
+            return Some(format!("'{}' is required when '{}' is '{}'", arg_def.name, other, value));
+        }
+    }
+    
+    if !arg_def.required_unless.is_empty() {
+        if arg_def.required_unless.iter().any(|other| seen_args.contains(other)) {
+            return None;
+        }
+        
+        let others: Vec<_> = arg_def.required_unless.iter().map(|o| format!("'{}'", o)).collect();
+        return Some(format!(
+            "'{}' is required unless {} is specified",
+            arg_def.name,
+            others.join(" or ")
+        ));
+    }
+    
+    if !arg_def.required {
+        return None;
+    }
+    
+    Some(match arg_def.arg_type {
+        ArgType::Positional { .. } => format!("missing required positional argument: {}", arg_def.name),
+        _ => format!("required argument '{}' not provided", arg_def.name),
+    })
+}
+
+/// Help lines describing the conditional requirements of an arg.
+fn requirement_lines(arg: &Arg) -> Vec<String> {
+    let pairs = |pairs: &[(String, String)]| -> String {
+        pairs.iter().map(|(a, v)| format!("{}={}", a, v)).collect::<Vec<_>>().join(", ")
+    };
+    let mut lines = Vec::new();
+    
+    if !arg.required_unless.is_empty() {
+        lines.push(format!("required unless: {}", arg.required_unless.join(", ")));
+    }
+    
+    if !arg.required_if.is_empty() {
+        lines.push(format!("required if: {}", pairs(&arg.required_if)));
+    }
+    
+    if !arg.depends_on_value.is_empty() {
+        lines.push(format!("depends on: {}", pairs(&arg.depends_on_value)));
+    }
+    
+    for (value, other) in &arg.requires_if {
+        lines.push(format!("requires {} when {} = '{}'", other, arg.name, value));
+    }
+    
+    lines
+}
+
+/// The possible values of `arg` as an aligned list with their aliases and help,
+/// empty when none of them has anything to add to the bare names.
+fn possible_value_lines(arg: &Arg) -> Vec<String> {
+    let shown: Vec<_> = arg.possible_values.iter().filter(|p| !p.hidden).collect();
+    if shown.iter().all(|p| p.help.is_empty() && p.aliases.is_empty()) {
+        return Vec::new();
+    }
+    
+    let names: Vec<String> = shown.iter()
+        .map(|p| std::iter::once(&p.name).chain(&p.aliases).cloned().collect::<Vec<_>>().join(", "))
+        .collect();
+    let width = names.iter().map(|n| n.chars().count()).max().unwrap_or(0);
+    
+    let mut lines = vec!["possible values:".to_string()];
+    for (names, possible) in names.iter().zip(&shown) {
+        lines.push(format!("  {:<width$}  {}", names, possible.help).trim_end().to_string());
+    }
+    lines
+}
+
+/// Names of the given args and of every arg in their `when` chains.
+fn collect_arg_names(args: &[Arg], names: &mut Vec<String>) {
+    for arg in args {
+        names.push(arg.name.clone());
+        for chain in &arg.children {
+            collect_arg_names(&chain.args, names);
+        }
+    }
+}
+
+/// Check a set of args that can be active together (`outer` being the args already
+/// active around them) for clashing names, flags and positional indices.
+fn check_arg_scope(scope: &str, outer: &[Arg], args: &[Arg], problems: &mut Vec<String>) {
+    for (i, arg) in args.iter().enumerate() {
+        let earlier = outer.iter().chain(&args[..i]);
+        let is_flag = matches!(arg.arg_type, ArgType::Flag | ArgType::Option);
+
+        for other in earlier {
+            let other_is_flag = matches!(other.arg_type, ArgType::Flag | ArgType::Option);
+
+            if other.name == arg.name {
+                problems.push(format!("{}: arg '{}' is defined more than once", scope, arg.name));
+            }
+
+            if is_flag && other_is_flag {
+                if let (Some(a), Some(b)) = (arg.short, other.short) {
+                    if a == b {
+                        problems.push(format!(
+                            "{}: short flag '-{}' is used by both '{}' and '{}'",
+                            scope, a, other.name, arg.name
+                        ));
+                    }
+                }
+
+                if let (Some(a), Some(b)) = (&arg.long, &other.long) {
+                    if a == b {
+                        problems.push(format!(
+                            "{}: long flag '--{}' is used by both '{}' and '{}'",
+                            scope, a, other.name, arg.name
+                        ));
+                    }
                 }
             }
         }
+    }
+
+    let mut indices: Vec<(usize, &str)> = args.iter()
+        .filter_map(|a| match a.arg_type {
+            ArgType::Positional { index } => Some((index, a.name.as_str())),
+            _ => None,
+        })
+        .collect();
+    indices.sort();
+
+    for pair in indices.windows(2) {
+        if pair[0].0 == pair[1].0 {
+            problems.push(format!(
+                "{}: positional index {} is used by both '{}' and '{}'",
+                scope, pair[0].0, pair[0].1, pair[1].1
+            ));
+        }
+    }
+
+    indices.dedup_by_key(|(index, _)| *index);
+    for (expected, (index, name)) in indices.iter().enumerate() {
+        if *index != expected {
+            problems.push(format!(
+                "{}: positional '{}' has index {} but there is no positional at index {}",
+                scope, name, index, expected
+            ));
+            break;
+        }
+    }
+
+    let variadics = outer.iter().chain(args)
+        .filter(|a| a.arg_type == ArgType::Variadic)
+        .count();
+    if variadics > 1 && args.iter().any(|a| a.arg_type == ArgType::Variadic) {
+        problems.push(format!("{}: more than one variadic arg is active at once", scope));
+    }
+}
+
+/// Check a single arg (and its `when` chains) against the names known in its command.
+fn check_arg(scope: &str, active: &[Arg], arg: &Arg, known: &[String], problems: &mut Vec<String>) {
+    let defaults = arg.default_value.iter()
+        .chain(arg.default_value_if.iter().map(|(_, _, default)| default))
+        .chain(&arg.default_missing_value);
+    for default in defaults.flat_map(|d| arg.split_value(d)) {
+        if !arg.possible_values.is_empty() && arg.possible_value(&default).is_none() {
+            problems.push(format!(
+                "{}: default value '{}' of '{}' is not one of its possible values ({})",
+                scope, default, arg.name, arg.visible_values().join(", ")
+            ));
+        } else if let Err(reason) = arg.run_validators(&default) {
+            problems.push(format!("{}: default value '{}' of '{}' is invalid: {}", scope, default, arg.name, reason));
+        }
+    }
+    
+    #[cfg(feature = "prompt")]
+    if arg.prompt.is_some() && arg.arg_type == ArgType::Flag {
+        problems.push(format!("{}: flag '{}' cannot have a prompt", scope, arg.name));
+    }
+    
+    if arg.value_delimiter.is_some() && arg.arg_type == ArgType::Flag {
+        problems.push(format!("{}: flag '{}' cannot have a value delimiter", scope, arg.name));
+    }
+    
+    if (arg.value_name.is_some() || arg.value_hint != ValueHint::Other) && arg.arg_type == ArgType::Flag {
+        problems.push(format!("{}: flag '{}' cannot have a value name or hint", scope, arg.name));
+    }
+    
+    let mut spellings = Vec::new();
+    for spelling in arg.possible_values.iter().flat_map(|p| std::iter::once(&p.name).chain(&p.aliases)) {
+        let key = if arg.ignore_case { spelling.to_ascii_lowercase() } else { spelling.clone() };
+        if spellings.contains(&key) {
+            problems.push(format!("{}: possible value '{}' of '{}' is given more than once", scope, spelling, arg.name));
+        }
+        spellings.push(key);
+    }
+    
+    if !arg.validators.is_empty() && arg.arg_type == ArgType::Flag {
+        problems.push(format!("{}: flag '{}' cannot have validators", scope, arg.name));
+    }
+    
+    if !arg.path_checks.is_empty() && (arg.arg_type == ArgType::Flag || arg.key_value.is_some()) {
+        problems.push(format!("{}: '{}' does not take a path, so it cannot have path checks", scope, arg.name));
+    }
+    
+    let checks = &arg.path_checks;
+    if checks.contains(&PathCheck::NotExists)
+        && checks.iter().any(|c| matches!(c, PathCheck::MustExist | PathCheck::IsFile | PathCheck::IsDir))
+    {
+        problems.push(format!("{}: '{}' must both exist and not exist", scope, arg.name));
+    }
+    
+    if let Some(separator) = arg.key_value {
+        if arg.arg_type != ArgType::Option {
+            problems.push(format!("{}: '{}' takes KEY{}VALUE pairs but is not an option", scope, arg.name, separator));
+        }
         
-        if !self.subcommands.is_empty() {
-            writeln!(out, "\ncommands:").unwrap();
-            for sub in &self.subcommands {
-                writeln!(out, "  {:<20} {}", sub.name, sub.about).unwrap();
+        if !arg.possible_values.is_empty() || !arg.children.is_empty() {
+            problems.push(format!(
+                "{}: '{}' takes KEY{}VALUE pairs and cannot have possible values or when chains (use allowed_keys)",
+                scope, arg.name, separator
+            ));
+        }
+        
+        for default in arg.default_value.iter().chain(arg.default_value_if.iter().map(|(_, _, default)| default)) {
+            if let Err(e) = arg.key_values(separator, std::slice::from_ref(default)) {
+                problems.push(format!("{}: default value of '{}' is invalid: {}", scope, arg.name, e));
             }
         }
+    } else if !arg.allowed_keys.is_empty() {
+        problems.push(format!("{}: '{}' has allowed keys but does not take KEY=VALUE pairs", scope, arg.name));
+    }
+    
+    if arg.default_missing_value.is_some() && arg.arg_type != ArgType::Option {
+        problems.push(format!(
+            "{}: '{}' has a default_missing_value but does not take a value",
+            scope, arg.name
+        ));
+    }
+
+    for dep in &arg.depends_on {
+        if !known.contains(dep) {
+            problems.push(format!("{}: '{}' depends on unknown arg '{}'", scope, arg.name, dep));
+        }
+    }
 
-        while out.ends_with('\n') || out.ends_with(' ') {
-            out.pop();
+    for conflict in &arg.conflicts_with {
+        if !known.contains(conflict) {
+            problems.push(format!("{}: '{}' conflicts with unknown arg '{}'", scope, arg.name, conflict));
+        }
+    }
+    
+    let conditions = arg.required_unless.iter()
+        .chain(arg.required_if.iter().map(|(other, _)| other))
+        .chain(arg.requires_if.iter().map(|(_, other)| other))
+        .chain(arg.depends_on_value.iter().map(|(other, _)| other))
+        .chain(arg.default_value_if.iter().map(|(other, _, _)| other));
+    for other in conditions {
+        if !known.contains(other) {
+            problems.push(format!("{}: '{}' has a requirement on unknown arg '{}'", scope, arg.name, other));
+        }
+    }
+
+    for chain in &arg.children {
+        if !arg.possible_values.is_empty() && arg.possible_value(&chain.when_value).is_none() {
+            problems.push(format!(
+                "{}: '{}' has a when chain for '{}' which is not one of its possible values ({})",
+                scope, arg.name, chain.when_value, arg.visible_values().join(", ")
+            ));
         }
 
-        println!("{}", out);
+        let chain_scope = format!("{} (when {} = '{}')", scope, arg.name, chain.when_value);
+        check_arg_scope(&chain_scope, active, &chain.args, problems);
+
+        let mut chain_active = active.to_vec();
+        chain_active.extend(chain.args.iter().cloned());
+        for child in &chain.args {
+            check_arg(&chain_scope, &chain_active, child, known, problems);
+        }
     }
 }
 
@@ -1008,113 +3202,1071 @@ impl Command {
 /// ```
 pub struct CliApp {
     root: Command,
+    version: Option<String>,
+    init: Option<InitHook>,
+    response_files: bool,
+    external_prefix: Option<String>,
+    allow_external: bool,
+    #[cfg(feature = "config")]
+    config_files: Vec<std::path::PathBuf>,
+    #[cfg(feature = "repl")]
+    shell: Option<String>,
+    #[cfg(feature = "repl")]
+    shell_history: Option<std::path::PathBuf>,
 }
 
-impl CliApp {
-    /// Create a new CLI application with a given name.
+impl CliApp {
+    /// Create a new CLI application with a given name.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let app = CliApp::new("myapp");
+    /// ```
+    pub fn new(name: impl Into<String>) -> Self {
+        Self::from_command(Command::new(name))
+    }
+    
+    /// Create a CLI application around an existing root command, e.g. one from [`LuhParser::command`].
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let app = CliApp::from_command(Command::new("myapp").arg(Arg::new("verbose")));
+    /// ```
+    pub fn from_command(root: Command) -> Self {
+        Self {
+            root,
+            version: None,
+            init: None,
+            response_files: false,
+            external_prefix: None,
+            allow_external: false,
+            #[cfg(feature = "config")]
+            config_files: Vec::new(),
+            #[cfg(feature = "repl")]
+            shell: None,
+            #[cfg(feature = "repl")]
+            shell_history: None,
+        }
+    }
+    
+    /// Set the about/description of the CLI application.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let app = CliApp::new("myapp").about("This is my CLI application");
+    /// ```
+    pub fn about(mut self, about: impl Into<String>) -> Self {
+        self.root = self.root.about(about);
+        self
+    }
+    
+    /// Add a global argument to the root command.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let app = CliApp::new("myapp")
+    ///     .arg(Arg::new("verbose").short('v').help("Enable verbose output"));
+    /// ```
+    pub fn arg(mut self, arg: Arg) -> Self {
+        self.root = self.root.arg(arg);
+        self
+    }
+    
+    /// Add an argument group to the root command, see [`ArgGroup`].
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let app = CliApp::new("fetch")
+    ///     .arg(Arg::new("file").takes_value())
+    ///     .arg(Arg::new("url").takes_value())
+    ///     .group(ArgGroup::new("input").args(["file", "url"]).required(true));
+    /// ```
+    pub fn group(mut self, group: ArgGroup) -> Self {
+        self.root = self.root.group(group);
+        self
+    }
+    
+    /// Add a subcommand to the CLI application.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let sub = Command::new("start").about("Start the app");
+    /// let app = CliApp::new("myapp").subcommand(sub);
+    /// ```
+    pub fn subcommand(mut self, cmd: Command) -> Self {
+        self.root = self.root.subcommand(cmd);
+        self
+    }
+    
+    /// Set the handler of the root command, run when no subcommand is given.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let app = CliApp::new("myapp").handler(|args| {
+    ///     println!("verbose: {}", args.flag("verbose"));
+    ///     LuhTwin::done()
+    /// });
+    /// ```
+    pub fn handler<F, R>(mut self, f: F) -> Self
+    where
+        F: Fn(&ParsedArgs) -> LuhTwin<R> + 'static,
+        R: IntoExitCode,
+    {
+        self.root = self.root.handler(f);
+        self
+    }
+    
+    /// Set the version shown by `--version` / `-V`.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let app = CliApp::new("myapp").version(env!("CARGO_PKG_VERSION"));
+    /// ```
+    pub fn version(mut self, version: impl Into<String>) -> Self {
+        self.version = Some(version.into());
+        self
+    }
+    
+    /// Read argument values from a configuration file (`config` feature).
+    ///
+    /// Can be called several times to build a search list, the first file that
+    /// exists is used and a missing file is not an error. `.toml` and `.json`
+    /// files are supported. Top-level keys are arg names of the root command,
+    /// tables (objects) are sections for subcommands, nested for nested commands:
+    ///
+    /// ```toml
+    /// verbose = true
+    ///
+    /// [config]
+    /// format = "json"
+    ///
+    /// [remote.add]
+    /// tags = ["a", "b"]
+    /// ```
+    ///
+    /// Precedence is command line > environment (`Arg::env`) > config file > default,
+    /// config values go through the same `possible_values` checks as everything
+    /// else and `ParsedArgs::value_source` reports `ValueSource::ConfigFile`.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let app = CliApp::new("myapp")
+    ///     .config_file("./myapp.toml")
+    ///     .config_file(home.join(".config/myapp/config.toml"));
+    /// ```
+    #[cfg(feature = "config")]
+    pub fn config_file(mut self, path: impl Into<std::path::PathBuf>) -> Self {
+        self.config_files.push(path.into());
+        self
+    }
+    
+    /// Add a built-in command that starts an interactive shell, see [`CliApp::run_shell`].
+    ///
+    /// ```ignore
+    /// // $ myapp shell
+    /// // myapp> remote add origin https://example.com
+    /// let app = CliApp::new("myapp").shell("shell");
+    /// ```
+    #[cfg(feature = "repl")]
+    pub fn shell(mut self, name: impl Into<String>) -> Self {
+        self.shell = Some(name.into());
+        self
+    }
+    
+    /// Set the file the shell history is kept in (default `~/.<app name>_history`).
+    #[cfg(feature = "repl")]
+    pub fn shell_history(mut self, path: impl Into<std::path::PathBuf>) -> Self {
+        self.shell_history = Some(path.into());
+        self
+    }
+    
+    /// Run unknown subcommands as `<prefix>-<name>` executables found on `PATH`.
+    ///
+    /// Lets others extend the application without recompiling it, the way `git`
+    /// runs `git-foo` for `git foo`. The executable gets the arguments after the
+    /// subcommand name and its exit code becomes the application's. The root
+    /// help lists every such executable under "external commands".
+    ///
+    /// Only applies when the root command takes no positional arguments.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// // $ myapp deploy --fast   runs   myapp-deploy --fast
+    /// let app = CliApp::new("myapp").external_subcommands("myapp");
+    /// ```
+    pub fn external_subcommands(mut self, prefix: impl Into<String>) -> Self {
+        self.external_prefix = Some(prefix.into());
+        self
+    }
+    
+    /// Hand unknown subcommands to the root handler instead of failing.
+    ///
+    /// The handler finds the name and the raw arguments after it in
+    /// [`ParsedArgs::external_subcommand`]. With [`CliApp::external_subcommands`]
+    /// set too, executables on `PATH` are tried first.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let app = CliApp::new("myapp")
+    ///     .allow_external_subcommands(true)
+    ///     .subcommand(Command::new("build").handler(build))
+    ///     .handler(|args| match args.external_subcommand() {
+    ///         Some((name, rest)) => run_plugin(name, rest),
+    ///         None => Ok(()),
+    ///     });
+    /// ```
+    pub fn allow_external_subcommands(mut self, allow: bool) -> Self {
+        self.allow_external = allow;
+        self
+    }
+    
+    /// Expand `@path` arguments into the arguments listed in that file.
+    ///
+    /// Off by default. Response files hold whitespace separated arguments with
+    /// shell-like quoting (`'...'`, `"..."`, backslash escapes) and `#` comments,
+    /// and may include further response files; include cycles are an error.
+    /// Errors point to the file and line of the offending token.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// // $ myapp build @release.args
+    /// let app = CliApp::new("myapp").response_files(true);
+    /// ```
+    pub fn response_files(mut self, enabled: bool) -> Self {
+        self.response_files = enabled;
+        self
+    }
+    
+    /// Set a hook that fills the [`Context`] before the selected handler runs.
+    ///
+    /// The hook receives the parsed arguments of the root command (the global
+    /// arguments) and runs once per invocation, only when a handler is about to run.
+    /// The interactive shell runs it once when it starts, not for every line.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let app = CliApp::new("myapp")
+    ///     .arg(Arg::new("config").takes_value())
+    ///     .init(|ctx: &mut Context, globals: &ParsedArgs| {
+    ///         ctx.insert(Config::load(globals.get("config"))?);
+    ///         Ok(())
+    ///     });
+    /// ```
+    pub fn init<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&mut Context, &ParsedArgs) -> LuhTwin<()> + 'static,
+    {
+        self.init = Some(Box::new(f));
+        self
+    }
+    
+    /// Check the whole command tree for definition mistakes.
+    ///
+    /// Reports every problem found (each prefixed with its command path) instead of
+    /// stopping at the first one: clashing short/long flags, duplicate arg or
+    /// subcommand names, positional indices with gaps or duplicates, default values
+    /// outside `possible_values`, `when` chains for impossible values and
+    /// `depends_on`/`conflicts_with` naming args that don't exist. `when` chains and
+    /// subcommands are checked too.
+    ///
+    /// Debug builds run this automatically before parsing and panic on mistakes.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// #[test]
+    /// fn cli_definitions_are_valid() {
+    ///     build_app().validate().unwrap();
+    /// }
+    /// ```
+    pub fn validate(&self) -> LuhTwin<()> {
+        let mut problems = Vec::new();
+        self.root.check_definitions(&self.root.name, &mut problems);
+
+        if self.allow_external && self.root.handler.is_none() {
+            problems.push(format!(
+                "{}: external subcommands are allowed but the root command has no handler",
+                self.root.name
+            ));
+        }
+
+        #[cfg(feature = "repl")]
+        if let Some(shell) = &self.shell {
+            if self.root.subcommands.iter().any(|s| &s.name == shell) {
+                problems.push(format!("{}: subcommand '{}' clashes with the shell command", self.root.name, shell));
+            }
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(at!(
+                "found {} problem(s) in the command definitions:\n  {}",
+                problems.len(),
+                problems.join("\n  ")
+            ).into())
+        }
+    }
+    
+    /// Run the CLI application, parsing command-line arguments from `std::env::args_os()`.
+    ///
+    /// This is the main entry point to execute the application. Returns the exit code
+    /// chosen by the handler (or `EXIT_SUCCESS` for help and version output), errors
+    /// are handed back to the caller instead of terminating the process.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let app = CliApp::new("myapp");
+    /// app.run()?;
+    /// ```
+    pub fn run(self) -> LuhTwin<i32> {
+        let matches = self.try_parse_from_os(std::env::args_os().skip(1))?;
+        self.dispatch(&matches, &mut Context::new())
+    }
+
+    /// Parse the given arguments and run the selected handler.
+    ///
+    /// `args` are the arguments after the program name, which makes this handy for
+    /// exercising the whole application in-process.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let code = app.run_from(["hello", "--name", "Alice"])?;
+    /// assert_eq!(code, EXIT_SUCCESS);
+    /// ```
+    pub fn run_from<I, S>(&self, args: I) -> LuhTwin<i32>
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.run_with_context(args, &mut Context::new())
+    }
+
+    /// Like [`CliApp::run_from`] but with a caller-provided [`Context`].
+    ///
+    /// Values already in the context are visible to the `init` hook and the
+    /// handler, which is handy for injecting test doubles.
     ///
     /// # Example
     ///
     /// ```ignore
-    /// let app = CliApp::new("myapp");
+    /// let mut ctx = Context::new();
+    /// ctx.insert(FakeDb::default());
+    /// app.run_with_context(["migrate"], &mut ctx)?;
     /// ```
-    pub fn new(name: impl Into<String>) -> Self {
-        Self {
-            root: Command::new(name),
+    pub fn run_with_context<I, S>(&self, args: I, ctx: &mut Context) -> LuhTwin<i32>
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let matches = self.try_parse_from(args)?;
+        self.dispatch(&matches, ctx)
+    }
+
+    /// Run the CLI application and terminate the process with a conventional exit code.
+    ///
+    /// Errors are printed to stderr. Usage errors (unknown commands, bad arguments)
+    /// exit with `EXIT_USAGE`, handler errors with `EXIT_FAILURE`, help and version
+    /// output with `EXIT_SUCCESS` and everything else with the code the handler returned.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// fn main() {
+    ///     CliApp::new("myapp")
+    ///         .subcommand(Command::new("start").handler(|_| Ok(())))
+    ///         .run_and_exit();
+    /// }
+    /// ```
+    pub fn run_and_exit(self) -> ! {
+        std::process::exit(self.exit_code(std::env::args_os().skip(1)))
+    }
+
+    /// Parse and run like `run_and_exit`, printing errors and returning the exit code.
+    fn exit_code<I, S>(&self, args: I) -> i32
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<OsString>,
+    {
+        let matches = match self.try_parse_from_os(args) {
+            Ok(matches) => matches,
+            Err(e) => {
+                eprintln!("error: {}", e);
+                eprintln!("run '{} help' for usage", self.root.name);
+                return EXIT_USAGE;
+            }
+        };
+
+        match self.dispatch(&matches, &mut Context::new()) {
+            Ok(code) => code,
+            Err(e) => {
+                eprintln!("error: {}", e);
+                EXIT_FAILURE
+            }
         }
     }
-    
-    /// Set the about/description of the CLI application.
+
+    /// Check if a string represents a help request.
+    fn is_help(&self, thing: &str) -> bool {
+        thing == "help" || thing == "--help" || thing == "-help"
+    }
+
+    /// Check if a string represents a version request.
+    fn is_version(&self, thing: &str) -> bool {
+        self.version.is_some() && (thing == "--version" || thing == "-V")
+    }
+
+    /// Parse the given arguments without running any handler.
+    ///
+    /// `args` are the arguments after the program name. Subcommands are followed
+    /// as deep as the arguments go, the returned [`Matches`] hold the selected
+    /// command path and its parsed arguments. Every error returned from here is
+    /// a usage error. A command given no arguments asks for its help when it has
+    /// no handler; one with a handler is parsed like any other invocation.
     ///
     /// # Example
     ///
     /// ```ignore
-    /// let app = CliApp::new("myapp").about("This is my CLI application");
+    /// let matches = app.try_parse_from(["config", "set", "theme", "dark"])?;
+    /// assert_eq!(matches.command_path(), ["moth", "config"]);
+    /// assert_eq!(matches.args().get("key").map(String::as_str), Some("theme"));
     /// ```
-    pub fn about(mut self, about: impl Into<String>) -> Self {
-        self.root = self.root.about(about);
-        self
+    pub fn try_parse_from<I, S>(&self, args: I) -> LuhTwin<Matches>
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.parse_tokens(args.into_iter().map(Into::into).collect(), &RawArgs::new())
     }
-    
-    /// Add a global argument to the root command.
+
+    /// Like [`CliApp::try_parse_from`] but for arguments that may not be valid UTF-8.
+    ///
+    /// Args marked with [`Arg::raw`] keep their exact value, see [`ParsedArgs::get_os`],
+    /// any other arg given a value that isn't UTF-8 is a usage error.
     ///
     /// # Example
     ///
     /// ```ignore
-    /// let app = CliApp::new("myapp")
-    ///     .arg(Arg::new("verbose").short('v').help("Enable verbose output"));
+    /// let matches = app.try_parse_from_os(std::env::args_os().skip(1))?;
+    /// let file = matches.args().get_path("file");
     /// ```
-    pub fn arg(mut self, arg: Arg) -> Self {
-        self.root = self.root.arg(arg);
-        self
+    pub fn try_parse_from_os<I, S>(&self, args: I) -> LuhTwin<Matches>
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<OsString>,
+    {
+        let (args, raw) = os::encode(args.into_iter().map(Into::into));
+        self.parse_tokens(args, &raw)
     }
-    
-    /// Add a subcommand to the CLI application.
+
+    /// Parse arguments after the app name, `raw` holding the originals of
+    /// arguments that are not valid UTF-8 (see the `os` module).
+    fn parse_tokens(&self, args: Vec<String>, raw: &RawArgs) -> LuhTwin<Matches> {
+        #[cfg(debug_assertions)]
+        if let Err(e) = self.validate() {
+            panic!("{}", e);
+        }
+
+        let mut blame = None;
+        if !self.response_files {
+            return self.parse_args(args, raw, &mut blame);
+        }
+
+        let tokens = response::expand(args)?;
+        let args = tokens.iter().map(|t| t.value.clone()).collect();
+        // tokens from files are text, only those from the command line can be raw
+        let raw = tokens.iter().enumerate()
+            .filter_map(|(i, t)| Some((i, raw.get(&t.arg?)?.clone())))
+            .collect();
+
+        self.parse_args(args, &raw, &mut blame).map_err(|e| {
+            match blame.and_then(|i| tokens.get(i)).and_then(|t| t.origin.as_ref()) {
+                Some((file, line)) => {
+                    let message = e.to_string();
+                    e.with_context(at!("{} (from {}:{})", message, file.display(), line))
+                }
+                None => e,
+            }
+        })
+    }
+
+    /// Parse arguments after the app name. On errors about a single argument,
+    /// `blame` holds its index.
+    fn parse_args(&self, args: Vec<String>, raw: &RawArgs, blame: &mut Option<usize>) -> LuhTwin<Matches> {
+        let mut command_path = vec![self.root.name.clone()];
+
+        if args.first().is_some_and(|a| self.is_help(a)) {
+            return Ok(Matches::request(Action::Help, command_path));
+        }
+
+        if args.first().is_some_and(|a| self.is_version(a)) {
+            return Ok(Matches::request(Action::Version, command_path));
+        }
+
+        #[cfg(feature = "repl")]
+        if args.len() == 1 && self.shell.as_ref() == args.first() {
+            return Ok(Matches::request(Action::Shell, command_path));
+        }
+
+        #[cfg(feature = "config")]
+        let config = ConfigLayer::load_first(&self.config_files, &self.root)?;
+        #[cfg(not(feature = "config"))]
+        let config = ConfigLayer::default();
+
+        let mut cmd = &self.root;
+        let mut rest = &args[..];
+        let mut first = 0;
+        let mut levels = Vec::new();
+
+        while let Some((split, sub)) = cmd.find_subcommand(rest) {
+            let mut parsed = cmd.parse(&rest[..split], first, config.section(&command_path), blame)?;
+            os::restore(&mut parsed, &cmd.args, raw)?;
+            paths::check(&parsed, &cmd.args, blame)?;
+            parsed.command_path = command_path.clone();
+            levels.push(parsed);
+
+            command_path.push(sub.name.clone());
+            cmd = sub;
+            rest = &rest[split + 1..];
+            first += split + 1;
+
+            if rest.first().is_some_and(|a| self.is_help(a)) {
+                return Ok(Matches::request(Action::Help, command_path));
+            }
+        }
+
+        if levels.is_empty() {
+            if let Some(matches) = self.parse_external(rest, &config, raw, blame)? {
+                return Ok(matches);
+            }
+        }
+
+        // a bare command without a handler shows its help, unless the environment
+        // or config file gave it values worth complaining about; one with a
+        // handler reports what is missing to run it
+        let section = config.section(&command_path);
+        let mut parsed = match cmd.parse(rest, first, section, blame) {
+            Ok(parsed) => parsed,
+            Err(_) if rest.is_empty() && cmd.handler.is_none() && !cmd.has_layered_values(section) => {
+                return Ok(Matches::request(Action::Help, command_path));
+            }
+            Err(e) => return Err(e),
+        };
+        
+        if rest.is_empty() && cmd.handler.is_none() {
+            return Ok(Matches::request(Action::Help, command_path));
+        }
+        
+        os::restore(&mut parsed, &cmd.args, raw)?;
+        paths::check(&parsed, &cmd.args, blame)?;
+        parsed.command_path = command_path;
+        levels.push(parsed);
+
+        if cmd.handler.is_none() && !cmd.subcommands.is_empty() {
+            *blame = Some(first);
+            return Err(at!("unknown command: {}", rest[0]).into());
+        }
+
+        Ok(Matches::link(levels))
+    }
+
+    /// Parse `args` of the root command as an external subcommand, if they name one.
+    fn parse_external(
+        &self,
+        args: &[String],
+        config: &ConfigLayer,
+        raw: &RawArgs,
+        blame: &mut Option<usize>,
+    ) -> LuhTwin<Option<Matches>> {
+        let takes_positionals = self.root.args.iter()
+            .any(|a| matches!(a.arg_type, ArgType::Positional { .. } | ArgType::Variadic));
+        if takes_positionals || (self.external_prefix.is_none() && !self.allow_external) {
+            return Ok(None);
+        }
+
+        let Some(split) = self.root.command_word(args) else {
+            return Ok(None);
+        };
+
+        let name = &args[split];
+        if raw.contains_key(&split) {
+            *blame = Some(split);
+            return Err(at!("unknown command: {}", name).into());
+        }
+        let on_path = self.external_prefix.as_deref()
+            .is_some_and(|prefix| external::find(prefix, name).is_some());
+        let action = match (on_path, self.allow_external) {
+            (true, _) => Action::External,
+            (false, true) => Action::Run,
+            (false, false) => {
+                *blame = Some(split);
+                return Err(at!("unknown command: {}", name).into());
+            }
+        };
+
+        let command_path = vec![self.root.name.clone()];
+        let mut parsed = self.root.parse(&args[..split], 0, config.section(&command_path), blame)?;
+        os::restore(&mut parsed, &self.root.args, raw)?;
+        paths::check(&parsed, &self.root.args, blame)?;
+        parsed.command_path = command_path;
+        let external_args = (split + 1..args.len())
+            .map(|i| raw.get(&i).cloned().unwrap_or_else(|| args[i].clone().into()))
+            .collect();
+        parsed.external = Some((name.clone(), external_args));
+
+        let mut matches = Matches::link(vec![parsed]);
+        matches.action = action;
+        Ok(Some(matches))
+    }
+
+    /// Find the command at the given path (starting with the application name).
+    fn command_at(&self, path: &[String]) -> Option<&Command> {
+        let mut cmd = &self.root;
+        for name in path.iter().skip(1) {
+            cmd = cmd.subcommands.iter().find(|s| &s.name == name)?;
+        }
+        Some(cmd)
+    }
+
+    /// Act on parsed matches, printing help or running the selected handler.
+    fn dispatch(&self, matches: &Matches, ctx: &mut Context) -> LuhTwin<i32> {
+        self.dispatch_with(matches, ctx, true)
+    }
+
+    /// `dispatch`, running `init` before the handler only when `init` is set.
+    fn dispatch_with(&self, matches: &Matches, ctx: &mut Context, init: bool) -> LuhTwin<i32> {
+        match self.prepare(matches, ctx)? {
+            Prepared::Done(code) => Ok(code),
+            Prepared::Run(Handler::Sync(handler)) => {
+                if init {
+                    self.run_init(&matches.root, ctx)?;
+                }
+                handler(ctx, &matches.args)
+            }
+            #[cfg(feature = "async")]
+            Prepared::Run(Handler::Async(_)) => Err(at!(
+                "command '{}' has an async handler, run it with CliApp::run_async",
+                matches.command_path().join(" ")
+            ).into()),
+        }
+    }
+
+    /// Everything `dispatch` does short of running the handler: print help or the
+    /// version, or hand back the selected handler. `ctx` is only for the shell.
+    #[cfg_attr(not(feature = "repl"), allow(unused_variables))]
+    fn prepare(&self, matches: &Matches, ctx: &mut Context) -> LuhTwin<Prepared<'_>> {
+        let command_path = matches.command_path();
+        let cmd = self.command_at(command_path)
+            .ok_or_else(|| at!("unknown command: {}", command_path.join(" ")))?;
+
+        match matches.action {
+            Action::Help => {
+                cmd.print_help(&command_path.join(" "));
+                if command_path.len() == 1 {
+                    let external = self.external_prefix.as_deref().map(external::list).unwrap_or_default();
+                    let external: Vec<_> = external.iter()
+                        .filter(|name| !cmd.subcommands.iter().any(|s| &&s.name == name))
+                        .collect();
+                    if !external.is_empty() {
+                        println!("\nexternal commands:");
+                        for name in external {
+                            println!("  {}", name);
+                        }
+                    }
+                    println!();
+                } else {
+                    println!("{}", cmd.usage);
+                }
+                Ok(Prepared::Done(EXIT_SUCCESS))
+            }
+            Action::Version => {
+                println!("{} {}", self.root.name, self.version.as_deref().unwrap_or_default());
+                Ok(Prepared::Done(EXIT_SUCCESS))
+            }
+            Action::External => {
+                let (name, args) = matches.args.external_subcommand()
+                    .ok_or_else(|| at!("no external subcommand to run"))?;
+                let program = self.external_prefix.as_deref()
+                    .and_then(|prefix| external::find(prefix, name))
+                    .ok_or_else(|| at!("unknown command: {}", name))?;
+
+                let status = std::process::Command::new(&program)
+                    .args(args)
+                    .status()
+                    .wrap(|| format!("failed to run {}", program.display()))?;
+
+                // killed by a signal: no code to pass on
+                Ok(Prepared::Done(status.code().unwrap_or(EXIT_FAILURE)))
+            }
+            #[cfg(feature = "repl")]
+            Action::Shell => self.start_shell(&matches.root, ctx).map(Prepared::Done),
+            Action::Run => {
+                if let Some(handler) = &cmd.handler {
+                    Ok(Prepared::Run(handler))
+                } else {
+                    Err(at!("no handler for command '{}'", cmd.name).into())
+                }
+            }
+        }
+    }
+
+    /// Run the `init` hook on the global arguments, right before a handler runs.
+    fn run_init(&self, root: &ParsedArgs, ctx: &mut Context) -> LuhTwin<()> {
+        match &self.init {
+            Some(init) => init(ctx, root),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(feature = "async")]
+impl CliApp {
+    /// Run the CLI application, awaiting async handlers.
+    ///
+    /// Works like [`CliApp::run`] but also runs handlers registered with
+    /// [`Command::async_handler`]. It is runtime agnostic: drive the returned
+    /// future with whatever executor the application already uses.
     ///
     /// # Example
     ///
     /// ```ignore
-    /// let sub = Command::new("start").about("Start the app");
-    /// let app = CliApp::new("myapp").subcommand(sub);
+    /// #[tokio::main]
+    /// async fn main() -> LuhTwin<()> {
+    ///     let app = CliApp::new("myapp")
+    ///         .subcommand(Command::new("fetch").async_handler(|args: ParsedArgs| async move {
+    ///             download(args.get("url")).await
+    ///         }));
+    ///
+    ///     app.run_async().await?;
+    ///     Ok(())
+    /// }
     /// ```
-    pub fn subcommand(mut self, cmd: Command) -> Self {
-        self.root = self.root.subcommand(cmd);
-        self
+    pub async fn run_async(self) -> LuhTwin<i32> {
+        let matches = self.try_parse_from_os(std::env::args_os().skip(1))?;
+        self.dispatch_async(matches, Context::new()).await
     }
-    
-    /// Run the CLI application, parsing command-line arguments from `std::env::args()`.
+
+    /// Parse the given arguments and run (or await) the selected handler.
+    ///
+    /// `args` are the arguments after the program name, see [`CliApp::run_from`].
+    pub async fn run_from_async<I, S>(&self, args: I) -> LuhTwin<i32>
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.run_with_context_async(args, Context::new()).await
+    }
+
+    /// Like [`CliApp::run_from_async`] but with a caller-provided [`Context`].
     ///
-    /// This is the main entry point to execute the application.
+    /// Values already in the context are visible to the `init` hook and the
+    /// handler. The context is moved into async handlers, see
+    /// [`Command::async_context_handler`].
     ///
     /// # Example
     ///
     /// ```ignore
-    /// let app = CliApp::new("myapp");
-    /// app.run();
+    /// let mut ctx = Context::new();
+    /// ctx.insert(FakeDb::default());
+    /// app.run_with_context_async(["sync"], ctx).await?;
     /// ```
-    pub fn run(self) -> LuhTwin<()> {
-        let args: Vec<String> = std::env::args().skip(1).collect();
-        self.run_with_args(&args)
+    pub async fn run_with_context_async<I, S>(&self, args: I, ctx: Context) -> LuhTwin<i32>
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let matches = self.try_parse_from(args)?;
+        self.dispatch_async(matches, ctx).await
     }
 
-    /// Check if a string represents a help request.
-    fn is_help(&self, thing: &str) -> bool {
-        thing == "help" || thing == "--help" || thing == "-help"
+    /// Act on parsed matches like `dispatch`, awaiting async handlers.
+    async fn dispatch_async(&self, matches: Matches, mut ctx: Context) -> LuhTwin<i32> {
+        match self.prepare(&matches, &mut ctx)? {
+            Prepared::Done(code) => Ok(code),
+            Prepared::Run(Handler::Sync(handler)) => {
+                self.run_init(&matches.root, &mut ctx)?;
+                handler(&mut ctx, &matches.args)
+            }
+            Prepared::Run(Handler::Async(handler)) => {
+                self.run_init(&matches.root, &mut ctx)?;
+                handler(ctx, matches.into_args()).await
+            }
+        }
+    }
+}
+
+/// Outcome of `CliApp::prepare`.
+enum Prepared<'a> {
+    /// Nothing left to run, exit with this code
+    Done(i32),
+    /// Run this handler
+    Run(&'a Handler),
+}
+
+/// What a parsed command line asks the application to do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    /// Run the handler of the selected command
+    Run,
+    /// Print the help of the selected command
+    Help,
+    /// Print the application version
+    Version,
+    /// Run the external subcommand executable (see `CliApp::external_subcommands`)
+    External,
+    /// Start the interactive shell (`repl` feature)
+    #[cfg(feature = "repl")]
+    Shell,
+}
+
+/// The result of [`CliApp::try_parse_from`]: the selected command and its parsed arguments.
+///
+/// # Provided Methods
+///
+/// - [`Matches::action`] – What the command line asks for (run, help or version).
+/// - [`Matches::command_path`] – Names of the selected command, starting with the app name.
+/// - [`Matches::args`] – Parsed arguments of the selected command.
+/// - [`Matches::root_args`] – Parsed arguments of the application itself.
+/// - [`Matches::into_args`] – Take the parsed arguments by value.
+#[derive(Debug, Clone)]
+pub struct Matches {
+    action: Action,
+    /// Arguments of the selected command, linked up through `ParsedArgs::parent`
+    args: ParsedArgs,
+    /// Arguments of the root command, linked down through `ParsedArgs::subcommand`
+    root: ParsedArgs,
+}
+
+impl Matches {
+    /// Matches for a help or version request, with no parsed arguments.
+    fn request(action: Action, command_path: Vec<String>) -> Self {
+        let args = ParsedArgs { command_path, ..Default::default() };
+        Self { action, root: args.clone(), args }
+    }
+
+    /// Matches for running a command, from the parsed arguments of every level
+    /// of the command path (root first).
+    fn link(mut levels: Vec<ParsedArgs>) -> Self {
+        for i in 1..levels.len() {
+            let parent = levels[i - 1].clone();
+            levels[i].parent = Some(Box::new(parent));
+        }
+
+        let mut root = levels.pop().unwrap_or_default();
+        let args = root.clone();
+        while let Some(mut parent) = levels.pop() {
+            let name = root.command_path.last().cloned().unwrap_or_default();
+            parent.subcommand = Some((name, Box::new(root)));
+            root = parent;
+        }
+
+        Self { action: Action::Run, args, root }
+    }
+
+    /// What the command line asks for (run, help or version).
+    pub fn action(&self) -> Action {
+        self.action
+    }
+
+    /// Names of the selected command, starting with the app name (e.g. `["moth", "config"]`).
+    pub fn command_path(&self) -> &[String] {
+        self.args.command_path()
+    }
+
+    /// Parsed arguments of the selected command.
+    pub fn args(&self) -> &ParsedArgs {
+        &self.args
+    }
+
+    /// Parsed arguments of the application itself, walk down to the selected
+    /// command with [`ParsedArgs::subcommand`].
+    pub fn root_args(&self) -> &ParsedArgs {
+        &self.root
+    }
+
+    /// Take the parsed arguments by value.
+    pub fn into_args(self) -> ParsedArgs {
+        self.args
     }
+}
+
+/// An enum whose variants are the possible values of an arg, usually through
+/// `#[derive(ValueEnum)]` (`derive` feature).
+///
+/// Each unit variant is named in kebab case (`DryRun` is `dry-run`) and its doc
+/// comment becomes the value's help; `#[luhcli(name = "...")]`, `help = "..."`,
+/// `alias = "..."` and `hide` fine-tune it. The enum must also be `Clone`.
+///
+/// # Example
+///
+/// ```ignore
+/// use luhcli::{Arg, ValueEnum};
+///
+/// #[derive(Clone, ValueEnum)]
+/// enum Mode {
+///     /// Skip the checks
+///     Fast,
+///     /// Ask before anything
+///     #[luhcli(alias = "safe")]
+///     Careful,
+/// }
+///
+/// let mode = Arg::positional("mode", 0).value_enum::<Mode>()
+///     .when_variant(Mode::Careful, [Arg::new("log").takes_value()]);
+/// // later
+/// let mode: Option<Mode> = parsed.get_enum("mode");
+/// ```
+///
+/// # Provided Methods
+///
+/// - [`ValueEnum::value_variants`] – Every variant, in the order they are offered.
+/// - [`ValueEnum::to_possible_value`] – The possible value a variant stands for.
+/// - [`ValueEnum::from_value`] – The variant a value or one of its aliases names.
+pub trait ValueEnum: Sized + Clone + 'static {
+    /// Every variant, in the order they are offered.
+    fn value_variants() -> &'static [Self];
+    
+    /// The possible value a variant stands for.
+    fn to_possible_value(&self) -> PossibleValue;
     
-    fn run_with_args(&self, args: &[String]) -> LuhTwin<()> {
-        if args.is_empty() || self.is_help(&args[0]) {
-            self.root.print_help(&self.root.name);
-            println!();
-            return Ok(());
+    /// The variant a value or one of its aliases names.
+    fn from_value(value: &str) -> Option<Self> {
+        Self::value_variants().iter()
+            .find(|variant| variant.to_possible_value().is_match(value, false))
+            .cloned()
+    }
+}
+
+/// A command line interface declared as a struct, usually through `#[derive(LuhParser)]`
+/// (`derive` feature).
+///
+/// Every named field becomes an argument named after the field (`dry_run` is
+/// `--dry-run`), doc comments become help text and `#[luhcli(...)]` fine-tunes it:
+///
+/// - `bool` fields are flags, `Option<T>` fields optional options, `Vec<T>`
+///   fields the variadic arg and any other `T: FromStr` a required option;
+///   `PathBuf` and `OsString` values are read with `Arg::raw`
+/// - `positional` makes the field a positional argument, in field order
+/// - `short`, `short = 'x'`, `long = "..."`, `help = "..."`, `default = "..."`,
+///   `env = "..."`, `possible_values("a", "b")` and `name = "..."` set the
+///   matching [`Arg`] builders
+/// - `value_enum` reads the field (or `Option`/`Vec` of it) as a [`ValueEnum`]
+/// - `subcommand` on a field of a [`LuhSubcommand`] type (or `Option` of one)
+///   adds its commands
+///
+/// On the struct, `name = "..."` (default: the package name), `about = "..."`
+/// (default: the doc comment) and `version = "..."` describe the application.
+///
+/// # Example
+///
+/// ```ignore
+/// use luhcli::{LuhParser, LuhSubcommand};
+///
+/// /// Manage remotes
+/// #[derive(LuhParser)]
+/// #[luhcli(name = "remote", version = "1.0")]
+/// struct Cli {
+///     /// Print more
+///     #[luhcli(short)]
+///     verbose: bool,
+///     #[luhcli(subcommand)]
+///     command: Action,
+/// }
+///
+/// #[derive(LuhSubcommand)]
+/// enum Action {
+///     /// Add a remote
+///     Add {
+///         #[luhcli(positional)]
+///         name: String,
+///         #[luhcli(default = "fetch", possible_values("fetch", "push"))]
+///         mode: String,
+///     },
+///     /// List remotes
+///     List,
+/// }
+///
+/// let cli = Cli::parse();
+/// ```
+pub trait LuhParser: Sized {
+    /// The command tree.
+    fn command() -> Command;
+
+    /// Build the value from the parsed root arguments, see [`Matches::root_args`].
+    fn from_args(args: &ParsedArgs) -> LuhTwin<Self>;
+
+    /// The application around [`LuhParser::command`].
+    fn app() -> CliApp {
+        CliApp::from_command(Self::command())
+    }
+
+    /// Parse the given arguments (after the program name).
+    ///
+    /// Help and version requests are printed and give `None`.
+    fn try_parse_from<I, S>(args: I) -> LuhTwin<Option<Self>>
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<OsString>,
+    {
+        let app = Self::app();
+        let matches = app.try_parse_from_os(args)?;
+
+        match matches.action() {
+            Action::Run => Self::from_args(matches.root_args()).map(Some),
+            _ => app.dispatch(&matches, &mut Context::new()).map(|_| None),
         }
-        
-        if let Some(subcmd) = self.root.subcommands.iter().find(|s| s.name == args[0]) {
-            let subcmd_args = &args[1..];
-            
-            if subcmd_args.is_empty() || self.is_help(&subcmd_args[0]) {
-                subcmd.print_help(&format!("{} {}", self.root.name, subcmd.name));
-                println!("{}", subcmd.usage);
-                return Ok(());
-            }
-            
-            let parsed = subcmd.parse(subcmd_args)?;
-            
-            if let Some(handler) = &subcmd.handler {
-                return handler(&parsed);
-            } else {
-                return Err(at!("no handler for command '{}'", subcmd.name).into());
+    }
+
+    /// Parse the given arguments, exiting like [`CliApp::run_and_exit`] on
+    /// errors and after printing help or the version.
+    fn parse_from<I, S>(args: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<OsString>,
+    {
+        match Self::try_parse_from(args) {
+            Ok(Some(parsed)) => parsed,
+            Ok(None) => std::process::exit(EXIT_SUCCESS),
+            Err(e) => {
+                eprintln!("error: {}", e);
+                eprintln!("run '{} help' for usage", Self::command().name);
+                std::process::exit(EXIT_USAGE);
             }
         }
-        
-        let parsed = self.root.parse(args)?;
-        
-        if let Some(handler) = &self.root.handler {
-            handler(&parsed)
-        } else {
-            error!("unknown command: {}", args[0]);
-            self.root.print_help(&self.root.name);
-            std::process::exit(1);
-        }
     }
+
+    /// Parse the arguments of the process, see [`LuhParser::parse_from`].
+    fn parse() -> Self {
+        Self::parse_from(std::env::args_os().skip(1))
+    }
+}
+
+/// A set of subcommands declared as an enum, usually through `#[derive(LuhSubcommand)]`
+/// (`derive` feature).
+///
+/// Each variant is a command named after it (`ListAll` is `list-all`, or
+/// `#[luhcli(name = "...")]`) with its doc comment as about text. Unit variants
+/// take no arguments, struct-like variants read their fields like
+/// [`LuhParser`] does, and a tuple variant wraps a `LuhParser` struct.
+pub trait LuhSubcommand: Sized {
+    /// One command per variant.
+    fn subcommands() -> Vec<Command>;
+
+    /// Build the variant for the subcommand `name` from its parsed arguments.
+    fn from_subcommand(name: &str, args: &ParsedArgs) -> LuhTwin<Self>;
 }
@@ -0,0 +1,115 @@
+//! Non-UTF-8 arguments, see `CliApp::try_parse_from_os` and `Arg::raw`.
+//!
+//! The parser works on `String`s, so an argument that isn't valid UTF-8 is
+//! handed to it lossily converted while its original is kept aside, under the
+//! index of its token. Once a command is parsed, values of raw args read from
+//! such tokens get their original `OsString` back and values of any other arg
+//! (or of an assignment) are an error.
+
+use std::collections::HashMap;
+use std::ffi::{OsStr, OsString};
+use luhtwin::{LuhTwin, at};
+use crate::{Arg, ArgType, ParsedArgs};
+
+/// Originals of the tokens that are not valid UTF-8, by token index.
+pub(crate) type RawArgs = HashMap<usize, OsString>;
+
+/// The arguments as `String`s, lossily converted, and the originals of those that aren't UTF-8.
+pub(crate) fn encode<I: IntoIterator<Item = OsString>>(args: I) -> (Vec<String>, RawArgs) {
+    let mut raw = RawArgs::new();
+    let args = args.into_iter()
+        .enumerate()
+        .map(|(i, arg)| match arg.into_string() {
+            Ok(arg) => arg,
+            Err(arg) => {
+                let lossy = arg.to_string_lossy().into_owned();
+                raw.insert(i, arg);
+                lossy
+            }
+        })
+        .collect();
+
+    (args, raw)
+}
+
+/// The exact value held by the token at `index` from byte `offset` on, when that token isn't UTF-8.
+pub(crate) fn original(raw: &RawArgs, index: usize, offset: usize) -> Option<OsString> {
+    let token = raw.get(&index)?;
+    let bytes = token.as_encoded_bytes();
+
+    // the value follows `--name=`, which is valid UTF-8 or it wouldn't have named an arg
+    let prefix = bytes.get(..offset).filter(|prefix| std::str::from_utf8(prefix).is_ok())?;
+    // SAFETY: splitting right after a valid UTF-8 prefix keeps the rest valid
+    // encoded bytes, see `OsStr::from_encoded_bytes_unchecked`
+    let value = unsafe { OsStr::from_encoded_bytes_unchecked(&bytes[prefix.len()..]) };
+    Some(value.to_os_string())
+}
+
+/// Give raw args of `parsed` their original values, erroring on non-UTF-8 values of other args.
+pub(crate) fn restore(parsed: &mut ParsedArgs, args: &[Arg], raw: &RawArgs) -> LuhTwin<()> {
+    if raw.is_empty() {
+        return Ok(());
+    }
+
+    let mut restored = Vec::new();
+    for (name, positions) in &parsed.positions {
+        let originals: Vec<_> = positions.iter()
+            .filter_map(|&(at, offset)| original(raw, at, offset))
+            .collect();
+        let Some(last) = originals.last() else {
+            continue;
+        };
+
+        let arg = find_arg(args, &|a| &a.name == name);
+        if arg.is_some_and(|a| a.key_value.is_some()) {
+            return Err(at!("the values of '{}' are not all valid UTF-8", name).into());
+        }
+        if !arg.is_some_and(|a| a.raw) {
+            return Err(at!("the value of '{}' is not valid UTF-8: '{}'", name, last.to_string_lossy()).into());
+        }
+
+        // only the last occurrence is the value
+        if positions.last().is_some_and(|&(at, offset)| original(raw, at, offset).is_some()) {
+            restored.push((name.clone(), last.clone()));
+        }
+    }
+    parsed.os_values.extend(restored);
+
+    for at in &parsed.assignment_positions {
+        if let Some(token) = raw.get(at) {
+            let token = token.to_string_lossy();
+            let (name, value) = token.split_once('=').unwrap_or_default();
+            return Err(at!("the value of assignment '{}' is not valid UTF-8: '{}'", name, value).into());
+        }
+    }
+
+    if parsed.variadic_positions.iter().any(|at| raw.contains_key(at)) {
+        let variadic = find_arg(args, &|a| a.arg_type == ArgType::Variadic);
+        // pieces of delimited values no longer match their tokens
+        let whole = variadic.is_some_and(|a| a.value_delimiter.is_none());
+        match variadic {
+            Some(variadic) if variadic.raw && whole => {}
+            variadic => {
+                let name = variadic.map_or("arguments", |a| a.name.as_str());
+                return Err(at!("the values of '{}' are not all valid UTF-8", name).into());
+            }
+        }
+
+        parsed.os_variadic = parsed.variadic_positions.iter()
+            .zip(&parsed.variadic)
+            .map(|(at, value)| raw.get(at).cloned().unwrap_or_else(|| value.into()))
+            .collect();
+    }
+
+    Ok(())
+}
+
+/// Find an arg, including args of conditional chains.
+fn find_arg<'a>(args: &'a [Arg], pred: &dyn Fn(&Arg) -> bool) -> Option<&'a Arg> {
+    args.iter().find_map(|arg| {
+        if pred(arg) {
+            return Some(arg);
+        }
+        arg.children.iter().find_map(|chain| find_arg(&chain.args, pred))
+    })
+}
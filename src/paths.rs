@@ -0,0 +1,86 @@
+//! Filesystem checks on path arguments, see `Arg::must_exist` and friends.
+//!
+//! They run once a command is parsed and its raw values are restored, so
+//! paths that are not valid UTF-8 are checked as given.
+
+use std::path::Path;
+use luhtwin::{LuhTwin, at};
+use crate::{Arg, ArgType, ParsedArgs, PathCheck};
+
+/// Run the path checks of every arg of `parsed` that has a value, `blame`
+/// holding the index of the offending token on errors.
+pub(crate) fn check(parsed: &ParsedArgs, args: &[Arg], blame: &mut Option<usize>) -> LuhTwin<()> {
+    // args of chains that didn't apply have no source
+    let active = all_args(args).filter(|a| parsed.value_source(&a.name).is_some());
+    for arg in active.filter(|a| !a.path_checks.is_empty()) {
+        if arg.arg_type == ArgType::Variadic {
+            for (i, value) in parsed.variadic_os().into_iter().enumerate() {
+                *blame = parsed.variadic_positions.get(i).copied();
+                check_path(arg, Path::new(value))?;
+            }
+        } else {
+            *blame = parsed.positions.get(&arg.name).and_then(|at| at.last()).map(|&(at, _)| at);
+            if arg.value_delimiter.is_some() {
+                for piece in parsed.get_many(&arg.name).unwrap_or_default() {
+                    check_path(arg, Path::new(piece))?;
+                }
+            } else if let Some(path) = parsed.get_path(&arg.name) {
+                check_path(arg, path)?;
+            }
+        }
+    }
+
+    *blame = None;
+    Ok(())
+}
+
+fn check_path(arg: &Arg, path: &Path) -> LuhTwin<()> {
+    let shown = path.display();
+
+    for check in &arg.path_checks {
+        match check {
+            PathCheck::MustExist | PathCheck::IsFile | PathCheck::IsDir if !path.exists() => {
+                return Err(at!("path '{}' for '{}' does not exist", shown, arg.name).into());
+            }
+            PathCheck::IsFile if !path.is_file() => {
+                return Err(at!("path '{}' for '{}' is not a file", shown, arg.name).into());
+            }
+            PathCheck::IsDir if !path.is_dir() => {
+                return Err(at!("path '{}' for '{}' is not a directory", shown, arg.name).into());
+            }
+            PathCheck::NotExists if path.exists() => {
+                return Err(at!("path '{}' for '{}' already exists", shown, arg.name).into());
+            }
+            PathCheck::ParentExists => {
+                // a bare file name lives in the current directory
+                let parent = path.parent().filter(|p| !p.as_os_str().is_empty());
+                if parent.is_some_and(|p| !p.is_dir()) {
+                    return Err(at!("parent directory of '{}' for '{}' does not exist", shown, arg.name).into());
+                }
+            }
+            PathCheck::Extension(extensions) => {
+                let matches = path.extension()
+                    .and_then(|e| e.to_str())
+                    .is_some_and(|e| extensions.iter().any(|x| x.eq_ignore_ascii_case(e)));
+                if !matches {
+                    return Err(at!(
+                        "path '{}' for '{}' must have one of the extensions: {}",
+                        shown,
+                        arg.name,
+                        extensions.join(", ")
+                    ).into());
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+/// The args and the args of their conditional chains.
+fn all_args(args: &[Arg]) -> Box<dyn Iterator<Item = &Arg> + '_> {
+    Box::new(args.iter().flat_map(|arg| {
+        std::iter::once(arg).chain(arg.children.iter().flat_map(|chain| all_args(&chain.args)))
+    }))
+}
@@ -0,0 +1,148 @@
+//! Interactive prompting for missing args, see `Arg::prompt`.
+
+use std::io::{BufRead, IsTerminal, Write};
+use luhtwin::{LuhTwin, Wrap, at};
+use crate::{Arg, ArgType};
+
+/// Stands in for the terminal in tests.
+#[cfg(test)]
+pub(crate) struct Script {
+    /// The answers typed, `None` when stdin isn't a terminal
+    pub(crate) input: Option<std::io::Cursor<String>>,
+    /// Everything the prompts wrote
+    pub(crate) shown: Vec<u8>,
+}
+
+#[cfg(test)]
+thread_local! {
+    pub(crate) static SCRIPT: std::cell::RefCell<Option<Script>> = const { std::cell::RefCell::new(None) };
+}
+
+/// Where questions are written and answers read.
+struct Terminal<'a> {
+    input: &'a mut dyn BufRead,
+    output: &'a mut dyn Write,
+    /// Whether this is the real terminal, whose echo can be turned off for secrets
+    tty: bool,
+}
+
+impl Terminal<'_> {
+    fn say(&mut self, text: &str) {
+        writeln!(self.output, "{}", text).ok();
+    }
+
+    /// Print `prompt` and read one line, erroring at end of input.
+    fn read_line(&mut self, prompt: &str, name: &str) -> LuhTwin<String> {
+        write!(self.output, "{}", prompt).ok();
+        self.output.flush().ok();
+
+        let mut line = String::new();
+        let read = self.input.read_line(&mut line)
+            .wrap(|| format!("failed to read a value for '{}'", name))?;
+
+        if read == 0 {
+            self.say("");
+            return Err(at!("no value given for '{}'", name).into());
+        }
+
+        Ok(line)
+    }
+
+    /// Like `read_line`, without echoing the answer on a real terminal.
+    fn read_secret(&mut self, prompt: &str, name: &str) -> LuhTwin<String> {
+        if !self.tty {
+            return self.read_line(prompt, name);
+        }
+
+        rpassword::prompt_password(prompt).wrap(|| format!("failed to read a value for '{}'", name))
+    }
+}
+
+/// Ask the user for the value of `arg`, re-asking until it is valid.
+///
+/// Returns `None` when the arg has no prompt or stdin / stderr is not a
+/// terminal, so the caller falls back to its usual error.
+pub(crate) fn ask(arg: &Arg) -> LuhTwin<Option<Vec<String>>> {
+    let Some(message) = &arg.prompt else {
+        return Ok(None);
+    };
+
+    #[cfg(test)]
+    if let Some(answer) = SCRIPT.with_borrow_mut(|script| {
+        let Script { input, shown } = script.as_mut()?;
+        Some(match input {
+            Some(input) => ask_on(arg, message, &mut Terminal { input, output: shown, tty: false }).map(Some),
+            None => Ok(None),
+        })
+    }) {
+        return answer;
+    }
+
+    if !std::io::stdin().is_terminal() || !std::io::stderr().is_terminal() {
+        return Ok(None);
+    }
+
+    let mut input = std::io::stdin().lock();
+    let mut output = std::io::stderr();
+    ask_on(arg, message, &mut Terminal { input: &mut input, output: &mut output, tty: true }).map(Some)
+}
+
+/// `ask` on a given terminal.
+fn ask_on(arg: &Arg, message: &str, terminal: &mut Terminal) -> LuhTwin<Vec<String>> {
+    if !arg.possible_values.is_empty() {
+        return choose(arg, message, terminal).map(|value| vec![value]);
+    }
+
+    loop {
+        let prompt = format!("{}: ", message);
+        let answer = if arg.secret {
+            terminal.read_secret(&prompt, &arg.name)?
+        } else {
+            terminal.read_line(&prompt, &arg.name)?
+        };
+
+        let values: Vec<String> = match arg.arg_type {
+            ArgType::Variadic => answer.split_whitespace().map(String::from).collect(),
+            _ => vec![answer.trim_end_matches(['\r', '\n']).to_string()],
+        };
+
+        if values.iter().all(String::is_empty) {
+            terminal.say("a value is required");
+            continue;
+        }
+
+        match values.iter().map(|v| arg.check_value(v, None)).collect() {
+            Ok(values) => return Ok(values),
+            Err(e) => terminal.say(&e.to_string()),
+        }
+    }
+}
+
+/// Pick one of the possible values, by number or by name (or alias).
+fn choose(arg: &Arg, message: &str, terminal: &mut Terminal) -> LuhTwin<String> {
+    let shown: Vec<_> = arg.possible_values.iter().filter(|p| !p.hidden).collect();
+
+    terminal.say(&format!("{}:", message));
+    for (n, value) in shown.iter().enumerate() {
+        if value.help.is_empty() {
+            terminal.say(&format!("  {}) {}", n + 1, value.name));
+        } else {
+            terminal.say(&format!("  {}) {} - {}", n + 1, value.name, value.help));
+        }
+    }
+
+    loop {
+        let answer = terminal.read_line(&format!("choose 1-{}: ", shown.len()), &arg.name)?;
+        let answer = answer.trim();
+
+        let picked = answer.parse::<usize>().ok()
+            .and_then(|n| n.checked_sub(1))
+            .and_then(|n| shown.get(n).copied())
+            .or_else(|| arg.possible_value(answer));
+
+        match picked {
+            Some(value) => return Ok(value.name.clone()),
+            None => terminal.say(&format!("'{}' is not one of the choices", answer)),
+        }
+    }
+}
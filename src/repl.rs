@@ -0,0 +1,239 @@
+//! Interactive shell over the command tree, see `CliApp::shell`.
+
+use std::path::PathBuf;
+use luhtwin::{LuhTwin, Wrap, at};
+use rustyline::completion::Completer;
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::history::DefaultHistory;
+use rustyline::validate::Validator;
+use rustyline::{Editor, Helper};
+use crate::{Arg, ArgType, CliApp, Command, Context, EXIT_SUCCESS, ParsedArgs, ValueHint, response};
+
+/// Words the shell understands on top of the command tree.
+const BUILTINS: [&str; 3] = ["help", "exit", "quit"];
+
+impl CliApp {
+    /// Read commands from the terminal and dispatch them until `exit`, `quit` or end of input.
+    ///
+    /// Each line is split with shell-style quoting and run like a one-shot
+    /// invocation, sharing `ctx` between commands. The [`CliApp::init`] hook runs
+    /// once before the first line, without global arguments. Errors are printed
+    /// and the loop goes on. History is kept in the file set with [`CliApp::shell_history`].
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let mut ctx = Context::new();
+    /// app.run_shell(&mut ctx)?;
+    /// ```
+    pub fn run_shell(&self, ctx: &mut Context) -> LuhTwin<i32> {
+        self.start_shell(&ParsedArgs::default(), ctx)
+    }
+
+    /// Run `init` with the global arguments the shell was started with, then the shell.
+    pub(crate) fn start_shell(&self, root: &ParsedArgs, ctx: &mut Context) -> LuhTwin<i32> {
+        self.run_init(root, ctx)?;
+
+        let mut editor = Editor::<Completions, DefaultHistory>::new()
+            .wrap(|| "failed to start the shell")?;
+        editor.set_helper(Some(Completions { root: &self.root }));
+
+        let history = self.history_path();
+        if let Some(path) = &history {
+            // no history yet is fine
+            let _ = editor.load_history(path);
+        }
+
+        let prompt = format!("{}> ", self.root.name);
+
+        loop {
+            let line = match editor.readline(&prompt) {
+                Ok(line) => line,
+                Err(ReadlineError::Interrupted) => continue,
+                Err(ReadlineError::Eof) => break,
+                Err(e) => return Err(e).wrap(|| "failed to read from the terminal"),
+            };
+
+            if !line.trim().is_empty() {
+                let _ = editor.add_history_entry(line.as_str());
+            }
+
+            match self.run_line(&line, ctx) {
+                Ok(Some(code)) if code != EXIT_SUCCESS => eprintln!("exited with code {}", code),
+                Ok(Some(_)) => {}
+                Ok(None) => break,
+                Err(e) => eprintln!("error: {}", e),
+            }
+        }
+
+        if let Some(path) = &history {
+            if let Err(e) = editor.save_history(path) {
+                eprintln!("could not save history to {}: {}", path.display(), e);
+            }
+        }
+
+        Ok(EXIT_SUCCESS)
+    }
+
+    /// Run one line of input, `None` when the shell should stop.
+    pub(crate) fn run_line(&self, line: &str, ctx: &mut Context) -> LuhTwin<Option<i32>> {
+        let words: Vec<String> = response::tokenize(line)
+            .map_err(|(problem, _)| at!("{}", problem))?
+            .into_iter()
+            .map(|(word, _, _)| word)
+            .collect();
+
+        match words.first().map(String::as_str) {
+            None => Ok(Some(EXIT_SUCCESS)),
+            Some("exit" | "quit") => Ok(None),
+            Some(first) if self.shell.as_deref() == Some(first) => Err(at!("already in the shell").into()),
+            // `help remote add` reads better than `remote add help`
+            Some("help") if words.len() > 1 => {
+                let mut words = words;
+                words.rotate_left(1);
+                self.run_words(words, ctx).map(Some)
+            }
+            Some(_) => self.run_words(words, ctx).map(Some),
+        }
+    }
+
+    /// Parse and dispatch one line's words, `init` having run when the shell started.
+    fn run_words(&self, words: Vec<String>, ctx: &mut Context) -> LuhTwin<i32> {
+        let matches = self.try_parse_from(words)?;
+        self.dispatch_with(&matches, ctx, false)
+    }
+
+    /// The history file: the one set with `shell_history`, else `~/.<app>_history`.
+    fn history_path(&self) -> Option<PathBuf> {
+        self.shell_history.clone().or_else(|| {
+            let home = std::env::var_os("HOME")?;
+            Some(PathBuf::from(home).join(format!(".{}_history", self.root.name)))
+        })
+    }
+}
+
+/// Tab completion from the `Command` / `Arg` / `ArgChain` definitions.
+pub(crate) struct Completions<'a> {
+    pub(crate) root: &'a Command,
+}
+
+impl Completions<'_> {
+    /// Candidates for the word being typed at the end of `line`.
+    pub(crate) fn candidates(&self, line: &str) -> Vec<String> {
+        let mut words: Vec<&str> = line.split_whitespace().collect();
+        let current = if line.is_empty() || line.ends_with(char::is_whitespace) {
+            ""
+        } else {
+            words.pop().unwrap_or_default()
+        };
+
+        let mut cmd = self.root;
+        let mut typed = Vec::new();
+        for word in &words {
+            match cmd.subcommands.iter().find(|s| s.name == *word) {
+                Some(sub) if !word.starts_with('-') => {
+                    cmd = sub;
+                    typed.clear();
+                }
+                _ => typed.push(*word),
+            }
+        }
+
+        let positionals: Vec<String> = typed.iter()
+            .filter(|w| !w.starts_with('-') && cmd.assignment(w).is_none())
+            .map(|w| w.to_string())
+            .collect();
+        let active = cmd.get_active_args(&positionals);
+
+        let previous = typed.last().copied().unwrap_or_default();
+        let option = active.iter().find(|a| {
+            a.arg_type == ArgType::Option
+                && (a.long.as_ref().is_some_and(|l| previous == format!("--{}", l))
+                    || a.short.is_some_and(|s| previous == format!("-{}", s)))
+        });
+
+        let mut candidates: Vec<String> = if let Some(option) = option {
+            values_of(option, current)
+        } else if current.starts_with('-') {
+            active.iter()
+                .filter(|a| matches!(a.arg_type, ArgType::Flag | ArgType::Option))
+                .filter_map(|a| a.long.as_ref().map(|l| format!("--{}", l)))
+                .collect()
+        } else {
+            let mut values: Vec<String> = cmd.subcommands.iter().map(|s| s.name.clone()).collect();
+
+            let next = active.iter()
+                .find(|a| a.arg_type == ArgType::Positional { index: positionals.len() })
+                .or_else(|| active.iter().find(|a| a.arg_type == ArgType::Variadic));
+            if let Some(arg) = next {
+                values.extend(values_of(arg, current));
+                values.extend(arg.children.iter().map(|c| c.when_value.clone()));
+            }
+
+            if words.is_empty() {
+                values.extend(BUILTINS.iter().map(|b| b.to_string()));
+            }
+            values
+        };
+
+        candidates.retain(|c| c.starts_with(current));
+        candidates.sort();
+        candidates.dedup();
+        candidates
+    }
+}
+
+/// Candidates for a value of `arg`: its possible values, or paths when its hint asks for them.
+fn values_of(arg: &Arg, current: &str) -> Vec<String> {
+    let mut values: Vec<String> = arg.visible_values().into_iter().map(String::from).collect();
+    match arg.value_hint {
+        ValueHint::FilePath | ValueHint::ExecutablePath => values.extend(paths(current, false)),
+        ValueHint::DirPath => values.extend(paths(current, true)),
+        _ => {}
+    }
+    values
+}
+
+/// Entries of the directory `current` is being typed in, directories ending in `/`.
+fn paths(current: &str, dirs_only: bool) -> Vec<String> {
+    let dir = current.rfind('/').map_or("", |i| &current[..=i]);
+    let hidden = current[dir.len()..].starts_with('.');
+    let Ok(entries) = std::fs::read_dir(if dir.is_empty() { "." } else { dir }) else {
+        return Vec::new();
+    };
+
+    entries.flatten()
+        .filter_map(|entry| {
+            let is_dir = entry.path().is_dir();
+            let name = entry.file_name().into_string().ok().filter(|n| hidden || !n.starts_with('.'))?;
+            (is_dir || !dirs_only).then(|| format!("{}{}{}", dir, name, if is_dir { "/" } else { "" }))
+        })
+        .collect()
+}
+
+impl Completer for Completions<'_> {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &rustyline::Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        let line = &line[..pos];
+        let start = line.rfind(char::is_whitespace).map_or(0, |i| i + 1);
+        Ok((start, self.candidates(line)))
+    }
+}
+
+impl Hinter for Completions<'_> {
+    type Hint = String;
+}
+
+impl Highlighter for Completions<'_> {}
+
+impl Validator for Completions<'_> {}
+
+impl Helper for Completions<'_> {}
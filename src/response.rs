@@ -0,0 +1,149 @@
+//! Response file (`@path`) expansion, see `CliApp::response_files`, and the
+//! shell-style tokenizer it shares with the REPL.
+
+use std::path::{Path, PathBuf};
+use luhtwin::{LuhTwin, Wrap, at};
+
+/// A command-line token, with the response file and line it was read from.
+pub(crate) struct Token {
+    pub(crate) value: String,
+    pub(crate) origin: Option<(PathBuf, usize)>,
+    /// Index of the command-line argument the token is, `None` when read from a file
+    pub(crate) arg: Option<usize>,
+}
+
+/// Replace every `@path` argument with the tokens read from that file.
+///
+/// Relative paths given on the command line are resolved against the working
+/// directory, paths inside a response file against that file's directory.
+pub(crate) fn expand(args: Vec<String>) -> LuhTwin<Vec<Token>> {
+    let mut out = Vec::new();
+    let mut stack = Vec::new();
+
+    for (i, arg) in args.into_iter().enumerate() {
+        match include_path(&arg) {
+            Some(path) => expand_file(Path::new(path), None, &mut stack, &mut out)?,
+            None => out.push(Token { value: arg, origin: None, arg: Some(i) }),
+        }
+    }
+
+    Ok(out)
+}
+
+/// The file named by an `@path` token, if it is one.
+fn include_path(arg: &str) -> Option<&str> {
+    arg.strip_prefix('@').filter(|path| !path.is_empty())
+}
+
+fn expand_file(
+    path: &Path,
+    included_from: Option<&(PathBuf, usize)>,
+    stack: &mut Vec<PathBuf>,
+    out: &mut Vec<Token>,
+) -> LuhTwin<()> {
+    let location = included_from
+        .map(|(file, line)| format!(" (included from {}:{})", file.display(), line))
+        .unwrap_or_default();
+
+    let text = std::fs::read_to_string(path)
+        .wrap(|| format!("cannot read response file {}{}", path.display(), location))?;
+
+    let canonical = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    if stack.contains(&canonical) {
+        let mut cycle: Vec<_> = stack.iter().map(|p| p.display().to_string()).collect();
+        cycle.push(canonical.display().to_string());
+        return Err(at!("response file cycle: {}{}", cycle.join(" -> "), location).into());
+    }
+
+    stack.push(canonical);
+    let dir = path.parent().unwrap_or(Path::new("")).to_path_buf();
+
+    let tokens = tokenize(&text)
+        .map_err(|(problem, line)| at!("{} in response file {}:{}", problem, path.display(), line))?;
+
+    for (value, line, quoted) in tokens {
+        let origin = (path.to_path_buf(), line);
+
+        match include_path(&value).filter(|_| !quoted) {
+            Some(nested) => expand_file(&dir.join(nested), Some(&origin), stack, out)?,
+            None => out.push(Token { value, origin: Some(origin), arg: None }),
+        }
+    }
+
+    stack.pop();
+    Ok(())
+}
+
+/// A token, the line it starts on and whether it starts quoted.
+pub(crate) type Word = (String, usize, bool);
+
+/// Split text into words, or the problem and its line.
+///
+/// Tokens are separated by whitespace. Single quotes keep everything literally,
+/// double quotes allow `\"` and `\\`, a backslash outside quotes escapes the next
+/// character and `#` at the start of a token comments out the rest of the line.
+pub(crate) fn tokenize(text: &str) -> Result<Vec<Word>, (&'static str, usize)> {
+    let mut tokens = Vec::new();
+    let mut chars = text.chars().peekable();
+    let mut line = 1;
+
+    let mut current: Option<Word> = None;
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\n' | ' ' | '\t' | '\r' => {
+                tokens.extend(current.take());
+            }
+            '#' if current.is_none() => {
+                while chars.peek().is_some_and(|&c| c != '\n') {
+                    chars.next();
+                }
+            }
+            '\\' => {
+                let token = current.get_or_insert_with(|| (String::new(), line, false));
+                match chars.next() {
+                    Some('\n') => line += 1,
+                    Some(escaped) => token.0.push(escaped),
+                    None => return Err(("trailing backslash", line)),
+                }
+            }
+            '\'' | '"' => {
+                let start = line;
+                let token = current.get_or_insert_with(|| (String::new(), line, true));
+                let mut closed = false;
+
+                while let Some(q) = chars.next() {
+                    match q {
+                        _ if q == c => {
+                            closed = true;
+                            break;
+                        }
+                        '\\' if c == '"' && matches!(chars.peek(), Some('"' | '\\')) => {
+                            token.0.extend(chars.next());
+                        }
+                        '\n' => {
+                            line += 1;
+                            token.0.push(q);
+                        }
+                        _ => token.0.push(q),
+                    }
+                }
+
+                if !closed {
+                    let problem = if c == '"' { "unterminated double quote" } else { "unterminated single quote" };
+                    return Err((problem, start));
+                }
+            }
+            _ => {
+                current.get_or_insert_with(|| (String::new(), line, false)).0.push(c);
+            }
+        }
+
+        if c == '\n' {
+            line += 1;
+        }
+    }
+
+    tokens.extend(current);
+    Ok(tokens)
+}
@@ -1 +1,1461 @@
-// no tests just trust me bro
+use std::cell::{Cell, RefCell};
+use std::fs;
+use std::path::PathBuf;
+use std::rc::Rc;
+use luhtwin::{LuhTwin, at};
+use crate::{Action, Arg, ArgGroup, CliApp, Command, Context, DuplicateKeys, EXIT_FAILURE, EXIT_SUCCESS, EXIT_USAGE, ParsedArgs, PossibleValue, ValueEnum, ValueHint, ValueSource};
+
+/// A fresh directory under the system temp dir, removed on drop.
+struct TempDir(PathBuf);
+
+impl TempDir {
+    fn new(name: &str) -> Self {
+        let dir = std::env::temp_dir().join(format!("luhcli-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        Self(dir)
+    }
+
+    fn file(&self, name: &str) -> String {
+        let path = self.0.join(name);
+        fs::write(&path, "").unwrap();
+        path.display().to_string()
+    }
+
+    fn dir(&self, name: &str) -> String {
+        let path = self.0.join(name);
+        fs::create_dir_all(&path).unwrap();
+        path.display().to_string()
+    }
+
+    fn missing(&self, name: &str) -> String {
+        self.0.join(name).display().to_string()
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+fn app(arg: Arg) -> CliApp {
+    CliApp::new("app").handler(|_| Ok(())).arg(arg)
+}
+
+fn error(app: &CliApp, args: &[&str]) -> String {
+    app.try_parse_from(args.iter().copied()).unwrap_err().to_string()
+}
+
+#[test]
+fn exit_codes() {
+    let codes = CliApp::new("app")
+        .subcommand(Command::new("ok").handler(|_| Ok(())))
+        .subcommand(Command::new("code").handler(|_| Ok(3)))
+        .subcommand(Command::new("byte").handler(|_| Ok(4u8)))
+        .subcommand(Command::new("fail").handler(|_| -> LuhTwin<()> { Err(at!("broken").into()) }));
+
+    assert_eq!(codes.run_from(["ok"]).unwrap(), EXIT_SUCCESS);
+    assert_eq!(codes.run_from(["code"]).unwrap(), 3);
+    assert_eq!(codes.run_from(["byte"]).unwrap(), 4);
+    assert_eq!(codes.run_from(["help"]).unwrap(), EXIT_SUCCESS);
+    assert_eq!(codes.run_from(["fail"]).unwrap_err().to_string(), "broken");
+    assert_eq!(codes.run_from(["nope"]).unwrap_err().to_string(), "unknown command: nope");
+}
+
+#[test]
+fn exit_codes_for_run_and_exit() {
+    let codes = CliApp::new("app")
+        .subcommand(Command::new("code").handler(|_| Ok(3)))
+        .subcommand(Command::new("fail").handler(|_| -> LuhTwin<()> { Err(at!("broken").into()) }));
+
+    assert_eq!(codes.exit_code(["code"]), 3);
+    assert_eq!(codes.exit_code(["fail"]), EXIT_FAILURE);
+    assert_eq!(codes.exit_code(["nope"]), EXIT_USAGE);
+    assert_eq!(codes.exit_code(["--version"]), EXIT_USAGE);
+    assert_eq!(codes.version("1.0").exit_code(["--version"]), EXIT_SUCCESS);
+}
+
+#[test]
+fn parse_without_running_handlers() {
+    let ran = Rc::new(Cell::new(false));
+    let seen = ran.clone();
+    let greeter = CliApp::new("greeter")
+        .subcommand(Command::new("hello")
+            .arg(Arg::new("name").takes_value())
+            .handler(move |_| { seen.set(true); Ok(()) }));
+
+    let matches = greeter.try_parse_from(["hello", "--name", "Alice"]).unwrap();
+    assert_eq!(matches.action(), Action::Run);
+    assert_eq!(matches.command_path(), ["greeter", "hello"]);
+    assert_eq!(matches.args().get("name").unwrap(), "Alice");
+    assert!(!ran.get());
+
+    assert_eq!(greeter.try_parse_from(["hello", "help"]).unwrap().action(), Action::Help);
+    assert!(greeter.try_parse_from(["hello", "--nope"]).is_err());
+    assert!(!ran.get());
+}
+
+#[test]
+fn run_from_dispatches() {
+    let names = Rc::new(RefCell::new(Vec::new()));
+    let seen = names.clone();
+    let greeter = CliApp::new("greeter")
+        .subcommand(Command::new("hello")
+            .arg(Arg::positional("name", 0))
+            .handler(move |args| {
+                seen.borrow_mut().push(args.pos(0).cloned().unwrap_or_default());
+                Ok(())
+            }));
+
+    greeter.run_from(["hello", "Alice"]).unwrap();
+    greeter.run_from(["hello", "Bob"]).unwrap();
+    assert_eq!(*names.borrow(), ["Alice", "Bob"]);
+}
+
+#[test]
+fn nested_matches() {
+    let remote = CliApp::new("git")
+        .arg(Arg::new("verbose").short('v'))
+        .subcommand(Command::new("remote")
+            .arg(Arg::new("dry-run"))
+            .subcommand(Command::new("add").arg(Arg::positional("name", 0)).handler(|_| Ok(()))));
+
+    let matches = remote.try_parse_from(["-v", "remote", "--dry-run", "add", "origin"]).unwrap();
+    let add = matches.args();
+    assert_eq!(add.command_path(), ["git", "remote", "add"]);
+    assert_eq!(add.pos(0).unwrap(), "origin");
+
+    let parent = add.parent().unwrap();
+    assert_eq!(parent.command_path(), ["git", "remote"]);
+    assert!(parent.flag("dry-run"));
+    assert!(parent.parent().unwrap().flag("verbose"));
+    assert!(parent.parent().unwrap().parent().is_none());
+
+    let (name, sub) = matches.root_args().subcommand().unwrap();
+    assert_eq!(name, "remote");
+    let (name, sub) = sub.subcommand().unwrap();
+    assert_eq!(name, "add");
+    assert_eq!(sub.pos(0).unwrap(), "origin");
+    assert!(sub.subcommand().is_none());
+}
+
+#[test]
+fn shared_handler_sees_command_path() {
+    let paths = Rc::new(RefCell::new(Vec::new()));
+    let handler = |seen: Rc<RefCell<Vec<String>>>| move |args: &ParsedArgs| -> LuhTwin<()> {
+        seen.borrow_mut().push(args.command_path().join(" "));
+        Ok(())
+    };
+    let tool = CliApp::new("tool")
+        .subcommand(Command::new("start").handler(handler(paths.clone())))
+        .subcommand(Command::new("stop").handler(handler(paths.clone())));
+
+    tool.run_from(["start"]).unwrap();
+    tool.run_from(["stop"]).unwrap();
+    assert_eq!(*paths.borrow(), ["tool start", "tool stop"]);
+}
+
+#[derive(Debug, PartialEq)]
+struct Db(String);
+
+fn with_db() -> CliApp {
+    CliApp::new("app")
+        .arg(Arg::new("db").takes_value().default_value("sqlite://app.db"))
+        .init(|ctx, globals| {
+            if !ctx.contains::<Db>() {
+                ctx.insert(Db(globals.get("db").cloned().unwrap_or_default()));
+            }
+            Ok(())
+        })
+        .subcommand(Command::new("url").context_handler(|ctx, _| {
+            let db = ctx.state_mut::<Db>()?;
+            db.0.push_str("?used");
+            Ok(())
+        }))
+}
+
+#[test]
+fn context_is_filled_by_init() {
+    let mut ctx = Context::new();
+    with_db().run_with_context(["--db", "pg://prod", "url"], &mut ctx).unwrap();
+    assert_eq!(ctx.get::<Db>(), Some(&Db("pg://prod?used".into())));
+
+    let mut ctx = Context::new();
+    with_db().run_with_context(["url"], &mut ctx).unwrap();
+    assert_eq!(ctx.remove::<Db>(), Some(Db("sqlite://app.db?used".into())));
+
+    // help doesn't run the hook
+    let mut ctx = Context::new();
+    with_db().run_with_context(["help"], &mut ctx).unwrap();
+    assert!(!ctx.contains::<Db>());
+}
+
+#[test]
+fn context_can_be_injected() {
+    let mut ctx = Context::new();
+    ctx.insert(Db("fake".into()));
+    with_db().run_with_context(["url"], &mut ctx).unwrap();
+    assert_eq!(ctx.get::<Db>(), Some(&Db("fake?used".into())));
+
+    let bare = CliApp::new("app").subcommand(Command::new("url").context_handler(|ctx, _| {
+        ctx.state::<Db>().map(|_| ())
+    }));
+    assert!(bare.run_from(["url"]).unwrap_err().to_string().contains("no 'luhcli::tests::Db' in the context"));
+}
+
+/// A single-threaded executor polling the future until it is done.
+#[cfg(feature = "async")]
+fn block_on<F: std::future::Future>(fut: F) -> F::Output {
+    use std::sync::Arc;
+    use std::task::{Context as TaskContext, Poll, Wake, Waker};
+
+    struct Noop;
+    impl Wake for Noop {
+        fn wake(self: Arc<Self>) {}
+    }
+
+    let waker = Waker::from(Arc::new(Noop));
+    let mut cx = TaskContext::from_waker(&waker);
+    let mut fut = std::pin::pin!(fut);
+    loop {
+        if let Poll::Ready(out) = fut.as_mut().poll(&mut cx) {
+            return out;
+        }
+    }
+}
+
+/// Pending once, so handlers really get suspended.
+#[cfg(feature = "async")]
+async fn yield_now() {
+    let mut yielded = false;
+    std::future::poll_fn(|cx| {
+        if yielded {
+            std::task::Poll::Ready(())
+        } else {
+            yielded = true;
+            cx.waker().wake_by_ref();
+            std::task::Poll::Pending
+        }
+    }).await
+}
+
+#[cfg(feature = "async")]
+fn async_app() -> CliApp {
+    CliApp::new("app")
+        .arg(Arg::new("db").takes_value().default_value("sqlite://app.db"))
+        .init(|ctx, globals| {
+            if !ctx.contains::<Db>() {
+                ctx.insert(Db(globals.get("db").cloned().unwrap_or_default()));
+            }
+            Ok(())
+        })
+        .subcommand(Command::new("fetch").async_handler(|args| async move {
+            yield_now().await;
+            Ok(args.get("db").map_or(0, |db| db.len() as i32))
+        }))
+        .subcommand(Command::new("url").async_context_handler(|ctx, _| async move {
+            yield_now().await;
+            let code = ctx.state::<Db>()?.0.len();
+            Ok(code as i32)
+        }))
+}
+
+#[cfg(feature = "async")]
+#[test]
+fn async_handlers() {
+    let tool = async_app();
+    assert_eq!(block_on(tool.run_from_async(["fetch"])).unwrap(), 0);
+    assert_eq!(block_on(tool.run_from_async(["--db", "pg://x", "url"])).unwrap(), 6);
+    assert_eq!(block_on(tool.run_from_async(["help"])).unwrap(), EXIT_SUCCESS);
+
+    let mut ctx = Context::new();
+    ctx.insert(Db("fake".into()));
+    assert_eq!(block_on(tool.run_with_context_async(["url"], ctx)).unwrap(), 4);
+}
+
+#[cfg(feature = "async")]
+#[test]
+fn async_handlers_need_run_async() {
+    let inits = Rc::new(Cell::new(0));
+    let seen = inits.clone();
+    let tool = async_app().init(move |_, _| { seen.set(seen.get() + 1); Ok(()) });
+
+    let err = tool.run_from(["url"]).unwrap_err().to_string();
+    assert!(err.contains("has an async handler"), "{}", err);
+    assert_eq!(inits.get(), 0);
+
+    block_on(tool.run_from_async(["fetch"])).unwrap();
+    assert_eq!(inits.get(), 1);
+}
+
+/// The problems `validate` reports, one per line.
+fn problems(app: &CliApp) -> Vec<String> {
+    let err = app.validate().unwrap_err().to_string();
+    err.lines().skip(1).map(|line| line.trim().to_string()).collect()
+}
+
+#[test]
+fn clashing_flags_are_a_definition_problem() {
+    let clashing = CliApp::new("app")
+        .arg(Arg::new("verbose").short('v'))
+        .arg(Arg::new("version").short('v'))
+        .subcommand(Command::new("run")
+            .arg(Arg::new("fast").long("quick"))
+            .arg(Arg::new("quick")));
+
+    assert_eq!(problems(&clashing), [
+        "app: short flag '-v' is used by both 'verbose' and 'version'",
+        "app run: long flag '--quick' is used by both 'fast' and 'quick'",
+    ]);
+}
+
+#[test]
+fn positional_indices_are_a_definition_problem() {
+    let gap = app(Arg::positional("a", 0)).arg(Arg::positional("c", 2));
+    assert_eq!(problems(&gap), ["app: positional 'c' has index 2 but there is no positional at index 1"]);
+
+    let twice = app(Arg::positional("a", 0)).arg(Arg::positional("b", 0));
+    assert_eq!(problems(&twice), ["app: positional index 0 is used by both 'a' and 'b'"]);
+
+    // chain indices continue after their parent
+    let chained = app(Arg::positional("action", 0).when("copy", [Arg::positional("from", 1)]));
+    assert_eq!(problems(&chained), [
+        "app (when action = 'copy'): positional 'from' has index 1 but there is no positional at index 0",
+    ]);
+}
+
+#[test]
+fn unknown_references_are_a_definition_problem() {
+    let unknown = app(Arg::new("out").takes_value().depends_on("format").conflicts_with("stdout"))
+        .subcommand(Command::new("sub").arg(Arg::new("x").required_if_eq("mode", "a")));
+
+    assert_eq!(problems(&unknown), [
+        "app: 'out' depends on unknown arg 'format'",
+        "app: 'out' conflicts with unknown arg 'stdout'",
+        "app sub: 'x' has a requirement on unknown arg 'mode'",
+    ]);
+    assert!(app(Arg::new("quiet")).validate().is_ok());
+}
+
+fn fetch() -> CliApp {
+    CliApp::new("fetch")
+        .handler(|_| Ok(()))
+        .arg(Arg::new("file").takes_value())
+        .arg(Arg::new("url").takes_value())
+        .arg(Arg::new("stdin"))
+        .arg(Arg::new("offline"))
+        .arg(Arg::new("retries").takes_value())
+        .group(ArgGroup::new("input").args(["file", "url", "stdin"]).required(true)
+            .conflicts_with("offline"))
+}
+
+#[test]
+fn groups() {
+    let fetch = fetch();
+    assert!(fetch.try_parse_from(["--url", "x"]).is_ok());
+    assert_eq!(error(&fetch, &["--retries", "3"]), "group 'input' requires exactly one of --file, --url, --stdin");
+    assert_eq!(
+        error(&fetch, &["--url", "x", "--stdin"]),
+        "--url and --stdin cannot be used together (group 'input' allows exactly one of --file, --url, --stdin)"
+    );
+    assert_eq!(error(&fetch, &["--stdin", "--offline"]), "group 'input' (--stdin) conflicts with 'offline'");
+
+    let edits = CliApp::new("edit")
+        .handler(|_| Ok(()))
+        .arg(Arg::new("add").takes_value())
+        .arg(Arg::new("remove").takes_value())
+        .arg(Arg::new("message").takes_value())
+        .group(ArgGroup::new("changes").args(["add", "remove"]).required(true).multiple(true).requires("message"));
+    assert!(edits.try_parse_from(["--add", "a", "--remove", "b", "--message", "m"]).is_ok());
+    assert_eq!(error(&edits, &["--add", "a"]), "group 'changes' (--add) requires 'message' to be specified");
+}
+
+#[test]
+fn bare_invocations() {
+    // the root can run on its own, so the missing group is an error
+    let fetch = fetch();
+    assert_eq!(error(&fetch, &[]), "group 'input' requires exactly one of --file, --url, --stdin");
+    assert_eq!(fetch.exit_code(Vec::<String>::new()), EXIT_USAGE);
+
+    // a command that can't run on its own shows its help
+    let remote = CliApp::new("git").subcommand(Command::new("remote")
+        .arg(Arg::new("name").takes_value().required(true))
+        .subcommand(Command::new("add").handler(|_| Ok(()))));
+    assert_eq!(remote.try_parse_from(["remote"]).unwrap().action(), Action::Help);
+    assert_eq!(remote.try_parse_from(Vec::<String>::new()).unwrap().action(), Action::Help);
+}
+
+#[test]
+fn defaults_for_absent_args() {
+    let out = app(Arg::new("output").takes_value().default_value("out.txt"))
+        .arg(Arg::positional("input", 0).default_value("-"));
+
+    let parsed = out.try_parse_from(Vec::<String>::new()).unwrap().into_args();
+    assert_eq!(parsed.get("output").unwrap(), "out.txt");
+    assert_eq!(parsed.pos(0).unwrap(), "-");
+    assert!(parsed.is_default("output") && parsed.is_default("input"));
+
+    let parsed = out.try_parse_from(["in.txt", "--output", "x"]).unwrap().into_args();
+    assert_eq!(parsed.value_source("output"), Some(ValueSource::CommandLine));
+    assert_eq!(parsed.pos(0).unwrap(), "in.txt");
+}
+
+#[test]
+fn positional_defaults_keep_their_index() {
+    let pair = app(Arg::positional("a", 0).required(false))
+        .arg(Arg::positional("b", 1).required(false).default_value("B"))
+        .arg(Arg::positional("c", 2).required(false).default_value("C"));
+
+    let parsed = pair.try_parse_from(Vec::<String>::new()).unwrap().into_args();
+    assert_eq!(parsed.pos(0), None);
+    assert_eq!(parsed.pos(1), None);
+    assert!(parsed.positional().is_empty());
+    assert_eq!(parsed.get("b").unwrap(), "B");
+
+    let parsed = pair.try_parse_from(["x"]).unwrap().into_args();
+    assert_eq!(parsed.positional(), ["x", "B", "C"]);
+    assert_eq!(parsed.pos(1).unwrap(), "B");
+}
+
+#[test]
+fn conditional_and_missing_value_defaults() {
+    let levels = app(Arg::new("mode").takes_value())
+        .arg(Arg::new("level").takes_value().default_value("3").default_value_if("mode", "fast", "1"))
+        .arg(Arg::new("color").takes_value().default_value("auto").default_missing_value("always"));
+    let parse = |args: &[&str]| levels.try_parse_from(args.iter().copied()).unwrap().into_args();
+
+    assert_eq!(parse(&[]).get("level").unwrap(), "3");
+    assert_eq!(parse(&["--mode", "fast"]).get("level").unwrap(), "1");
+    assert_eq!(parse(&["--mode", "fast", "--level", "5"]).get("level").unwrap(), "5");
+
+    assert_eq!(parse(&[]).get("color").unwrap(), "auto");
+    assert_eq!(parse(&["--color"]).get("color").unwrap(), "always");
+    assert_eq!(parse(&["--color", "--mode", "x"]).get("color").unwrap(), "always");
+    assert_eq!(parse(&["--color", "never"]).get("color").unwrap(), "never");
+}
+
+#[test]
+fn defaults_apply_before_requirements() {
+    let format = || Arg::new("format").takes_value().default_value("csv");
+    let delim = || Arg::new("delim").takes_value().required_if_eq("format", "csv");
+    let expected = "'delim' is required when 'format' is 'csv'";
+
+    // whichever is declared first
+    assert_eq!(error(&app(format()).arg(delim()), &[]), expected);
+    assert_eq!(error(&app(delim()).arg(format()), &[]), expected);
+    assert!(app(delim()).arg(format()).try_parse_from(["--format", "json"]).is_ok());
+
+    // conditional defaults see plain ones, wherever they are declared
+    let level = Arg::new("level").takes_value().default_value_if("format", "csv", "1");
+    let parsed = app(level).arg(format()).try_parse_from(Vec::<String>::new()).unwrap();
+    assert_eq!(parsed.args().get("level").unwrap(), "1");
+}
+
+#[test]
+fn required_unless_present() {
+    let login = app(Arg::new("token").takes_value().required_unless_present("config").required_unless_present("anonymous"))
+        .arg(Arg::new("config").takes_value().default_value("app.toml"))
+        .arg(Arg::new("anonymous"));
+
+    assert_eq!(error(&login, &[]), "'token' is required unless 'config' or 'anonymous' is specified");
+    assert!(login.try_parse_from(["--config", "x.toml"]).is_ok());
+    assert!(login.try_parse_from(["--anonymous"]).is_ok());
+    assert!(login.try_parse_from(["--token", "t"]).is_ok());
+}
+
+#[test]
+fn required_if() {
+    let export = app(Arg::new("format").takes_value())
+        .arg(Arg::new("delim").takes_value().required_if_eq("format", "csv"))
+        .arg(Arg::new("sheet").takes_value().required_if_any([("format", "xlsx"), ("format", "ods")]));
+
+    assert!(export.try_parse_from(["--format", "json"]).is_ok());
+    assert_eq!(error(&export, &["--format", "csv"]), "'delim' is required when 'format' is 'csv'");
+    assert!(export.try_parse_from(["--format", "csv", "--delim", ";"]).is_ok());
+    assert_eq!(error(&export, &["--format", "ods"]), "'sheet' is required when 'format' is 'ods'");
+    assert!(export.try_parse_from(["--format", "xlsx", "--sheet", "1"]).is_ok());
+}
+
+#[test]
+fn requires_if() {
+    let export = || app(Arg::new("format").takes_value().requires_if("csv", "delim"))
+        .arg(Arg::new("delim").takes_value());
+
+    assert!(export().try_parse_from(["--format", "json"]).is_ok());
+    assert_eq!(error(&export(), &["--format", "csv"]), "'delim' is required when 'format' is 'csv'");
+    assert!(export().try_parse_from(["--format", "csv", "--delim", ";"]).is_ok());
+
+    // the trigger's default counts, and so does a default of the required arg
+    let defaulted = app(Arg::new("format").takes_value().default_value("csv").requires_if("csv", "delim"))
+        .arg(Arg::new("delim").takes_value());
+    assert_eq!(error(&defaulted, &[]), "'delim' is required when 'format' is 'csv'");
+    let satisfied = app(Arg::new("format").takes_value().default_value("csv").requires_if("csv", "delim"))
+        .arg(Arg::new("delim").takes_value().default_value(","));
+    assert!(satisfied.try_parse_from(Vec::<String>::new()).is_ok());
+}
+
+#[test]
+fn depends_on_value() {
+    let export = |format: Arg| app(format)
+        .arg(Arg::new("delim").takes_value().depends_on_value("format", "csv"));
+
+    let plain = export(Arg::new("format").takes_value());
+    assert!(plain.try_parse_from(["--format", "csv", "--delim", ";"]).is_ok());
+    assert_eq!(error(&plain, &["--delim", ";"]), "'delim' requires 'format' to be 'csv' (not specified)");
+    assert_eq!(
+        error(&plain, &["--format", "json", "--delim", ";"]),
+        "'delim' requires 'format' to be 'csv' (got 'json')"
+    );
+
+    // only when given, and against the default too
+    let defaulted = export(Arg::new("format").takes_value().default_value("csv"));
+    assert!(defaulted.try_parse_from(["--delim", ";"]).is_ok());
+    assert!(defaulted.try_parse_from(["--format", "json"]).is_ok());
+}
+
+#[test]
+fn depends_on_and_conflicts_with_defaults() {
+    let out = app(Arg::new("compress").depends_on("level").conflicts_with("raw"))
+        .arg(Arg::new("level").takes_value().default_value("6"))
+        .arg(Arg::new("raw").default_value("false"));
+
+    // a default satisfies a dependency but never conflicts
+    assert!(out.try_parse_from(["--compress"]).is_ok());
+    assert_eq!(error(&out, &["--compress", "--raw"]), "'compress' conflicts with 'raw'");
+}
+
+#[cfg(feature = "config")]
+fn configured(dir: &TempDir, name: &str, text: &str) -> CliApp {
+    let path = dir.0.join(name);
+    fs::write(&path, text).unwrap();
+
+    CliApp::new("app")
+        .config_file(dir.0.join("missing.toml"))
+        .config_file(path)
+        .arg(Arg::new("verbose"))
+        .arg(Arg::new("level").takes_value().default_value("1").env("LUHCLI_TEST_CONFIG_LEVEL"))
+        .arg(Arg::new("color").takes_value().default_value("auto"))
+        .subcommand(Command::new("remote")
+            .arg(Arg::new("format").takes_value().possible_values(["json", "text"]))
+            .subcommand(Command::new("add")
+                .arg(Arg::variadic("tags"))
+                .handler(|_| Ok(()))))
+}
+
+#[cfg(feature = "config")]
+#[test]
+fn config_precedence() {
+    let dir = TempDir::new("config-precedence");
+    let cfg = configured(&dir, "app.toml", "verbose = true\nlevel = 2\n");
+    let root = |args: &[&str]| {
+        let matches = cfg.try_parse_from(args.iter().copied().chain(["remote", "add"])).unwrap();
+        matches.root_args().clone()
+    };
+
+    let parsed = root(&[]);
+    assert!(parsed.flag("verbose"));
+    assert_eq!(parsed.get("level").unwrap(), "2");
+    assert_eq!(parsed.value_source("level"), Some(ValueSource::ConfigFile));
+    assert_eq!(parsed.get("color").unwrap(), "auto");
+    assert_eq!(parsed.value_source("color"), Some(ValueSource::Default));
+
+    std::env::set_var("LUHCLI_TEST_CONFIG_LEVEL", "3");
+    let from_env = root(&[]);
+    let from_line = root(&["--level", "4"]);
+    std::env::remove_var("LUHCLI_TEST_CONFIG_LEVEL");
+
+    assert_eq!(from_env.get("level").unwrap(), "3");
+    assert_eq!(from_env.value_source("level"), Some(ValueSource::Env));
+    assert_eq!(from_line.get("level").unwrap(), "4");
+    assert_eq!(from_line.value_source("level"), Some(ValueSource::CommandLine));
+}
+
+#[cfg(feature = "config")]
+#[test]
+fn config_sections() {
+    let dir = TempDir::new("config-sections");
+    let cfg = configured(&dir, "app.json", r#"{"remote": {"format": "json", "add": {"tags": ["a", "b"]}}}"#);
+
+    let matches = cfg.try_parse_from(["remote", "add"]).unwrap();
+    assert_eq!(matches.args().variadic(), ["a", "b"]);
+    assert_eq!(matches.args().parent().unwrap().get("format").unwrap(), "json");
+    assert_eq!(cfg.try_parse_from(["remote", "add", "c"]).unwrap().args().variadic(), ["c"]);
+
+    let bad = configured(&dir, "bad.toml", "[remote]\nformat = \"yaml\"\n");
+    let err = error(&bad, &["remote", "add"]);
+    assert!(err.contains("invalid value 'yaml' for 'format' (from config file"), "{}", err);
+    assert!(err.contains("bad.toml [remote])"), "{}", err);
+
+    let unknown = configured(&dir, "unknown.toml", "[deploy]\nfast = true\n");
+    assert!(error(&unknown, &["remote", "add"]).contains("unknown section 'deploy'"));
+    let unknown = configured(&dir, "unknown.toml", "[remote]\nlevel = 1\n");
+    assert!(error(&unknown, &["remote", "add"]).contains("unknown key 'level'"));
+}
+
+/// An app reading response files, with `files` written to `dir`.
+fn responding(dir: &TempDir, files: &[(&str, &str)]) -> CliApp {
+    for (name, text) in files {
+        let path = dir.0.join(name);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, text).unwrap();
+    }
+
+    CliApp::new("app")
+        .response_files(true)
+        .subcommand(Command::new("build")
+            .arg(Arg::new("format").takes_value().possible_values(["json", "csv"]))
+            .arg(Arg::new("config").takes_value().must_exist())
+            .arg(Arg::variadic("targets"))
+            .handler(|_| Ok(())))
+}
+
+#[test]
+fn response_files() {
+    let dir = TempDir::new("response-files");
+    let tool = responding(&dir, &[
+        ("main.args", "build\n# the targets\n@nested/more.args 'with space' \"q\\\"uote\"\n"),
+        ("nested/more.args", "--format csv app\nlib"),
+    ]);
+
+    let at = format!("@{}", dir.0.join("main.args").display());
+    let matches = tool.try_parse_from([at.as_str(), "last"]).unwrap();
+    assert_eq!(matches.args().get("format").unwrap(), "csv");
+    assert_eq!(matches.args().variadic(), ["app", "lib", "with space", "q\"uote", "last"]);
+
+    let tool = responding(&dir, &[("a.args", "build @b.args"), ("b.args", "\n@a.args")]);
+    let err = error(&tool, &[&format!("@{}", dir.0.join("a.args").display())]);
+    assert!(err.starts_with("response file cycle: "), "{}", err);
+    assert!(err.ends_with(&format!("a.args (included from {}:2)", dir.0.join("b.args").display())), "{}", err);
+
+    let tool = responding(&dir, &[("open.args", "build\n'oops")]);
+    let err = error(&tool, &[&format!("@{}", dir.0.join("open.args").display())]);
+    assert_eq!(err, format!("unterminated single quote in response file {}:2", dir.0.join("open.args").display()));
+}
+
+#[test]
+fn response_file_errors_point_to_the_token() {
+    let dir = TempDir::new("response-blame");
+    let tool = responding(&dir, &[
+        ("bad.args", "build\n--format\nyaml"),
+        ("words.args", "format yaml"),
+        ("command.args", "\n\ndeploy"),
+        ("config.args", "build --format json\n--config missing.toml"),
+    ]);
+    let file = |name: &str| dir.0.join(name).display().to_string();
+
+    assert_eq!(
+        error(&tool, &[&format!("@{}", file("bad.args"))]),
+        format!("invalid value 'yaml' for 'format'. possible values: json, csv (from {}:3)", file("bad.args"))
+    );
+
+    // the bad value is on the command line, not in the file quoting the same words
+    assert_eq!(
+        error(&tool, &["build", &format!("@{}", file("words.args")), "--format", "yaml"]),
+        "invalid value 'yaml' for 'format'. possible values: json, csv"
+    );
+
+    assert_eq!(
+        error(&tool, &[&format!("@{}", file("command.args"))]),
+        format!("unknown command: deploy (from {}:3)", file("command.args"))
+    );
+
+    let err = error(&tool, &[&format!("@{}", file("config.args"))]);
+    assert!(err.starts_with("path 'missing.toml' for 'config' does not exist"), "{}", err);
+    assert!(err.ends_with(&format!("(from {}:2)", file("config.args"))), "{}", err);
+}
+
+/// Run `f` with `input` typed at any prompt (`None` for a stdin that isn't a
+/// terminal), returning its result and what the prompts showed.
+#[cfg(feature = "prompt")]
+fn typing<T>(input: Option<&str>, f: impl FnOnce() -> T) -> (T, String) {
+    use crate::prompt::{SCRIPT, Script};
+
+    SCRIPT.set(Some(Script { input: input.map(|i| std::io::Cursor::new(i.to_string())), shown: Vec::new() }));
+    let result = f();
+    let shown = SCRIPT.take().unwrap().shown;
+    (result, String::from_utf8(shown).unwrap())
+}
+
+#[cfg(feature = "prompt")]
+#[test]
+fn prompts_ask_until_valid() {
+    let port = app(Arg::new("port").takes_value().required(true).prompt("Port").range(1..=65535));
+    let (parsed, shown) = typing(Some("\nhttp\n8080\n"), || port.try_parse_from(Vec::<String>::new()));
+    let parsed = parsed.unwrap().into_args();
+    assert_eq!(parsed.get("port").unwrap(), "8080");
+    assert_eq!(parsed.value_source("port"), Some(ValueSource::Prompt));
+    let lines: Vec<_> = shown.split("Port: ").collect();
+    assert_eq!(lines.len(), 4, "{}", shown);
+    assert_eq!(lines[1], "a value is required\n");
+    assert!(lines[2].starts_with("invalid value 'http' for 'port'"), "{}", shown);
+
+    let (parsed, _) = typing(Some(""), || port.try_parse_from(Vec::<String>::new()));
+    assert_eq!(parsed.unwrap_err().to_string(), "no value given for 'port'");
+
+    let token = app(Arg::new("token").takes_value().required(true).prompt("Token").secret());
+    let (parsed, _) = typing(Some("s3cret\n"), || token.try_parse_from(Vec::<String>::new()));
+    assert_eq!(parsed.unwrap().args().get("token").unwrap(), "s3cret");
+}
+
+#[cfg(feature = "prompt")]
+#[test]
+fn prompts_offer_possible_values() {
+    let format = app(Arg::new("format").takes_value().required(true).prompt("Format")
+        .possible_values([PossibleValue::new("json").help("machine readable"), PossibleValue::new("text").alias("txt")]));
+
+    let (parsed, shown) = typing(Some("3\ntxt\n"), || format.try_parse_from(Vec::<String>::new()));
+    assert_eq!(parsed.unwrap().args().get("format").unwrap(), "text");
+    assert_eq!(shown, [
+        "Format:",
+        "  1) json - machine readable",
+        "  2) text",
+        "choose 1-2: '3' is not one of the choices",
+        "choose 1-2: ",
+    ].join("\n"));
+
+    let (parsed, _) = typing(Some("1\n"), || format.try_parse_from(Vec::<String>::new()));
+    assert_eq!(parsed.unwrap().args().get("format").unwrap(), "json");
+}
+
+#[cfg(feature = "prompt")]
+#[test]
+fn prompted_positionals_keep_their_index() {
+    let copy = app(Arg::positional("from", 0).prompt("From"))
+        .arg(Arg::positional("to", 1).prompt("To"))
+        .arg(Arg::variadic("rest").required(true).prompt("Rest"));
+
+    let (parsed, _) = typing(Some("b.txt\nc d\n"), || copy.try_parse_from(["a.txt"]));
+    let parsed = parsed.unwrap().into_args();
+    assert_eq!(parsed.positional(), ["a.txt", "b.txt"]);
+    assert_eq!(parsed.get("to").unwrap(), "b.txt");
+    assert_eq!(parsed.variadic(), ["c", "d"]);
+
+    let (parsed, _) = typing(Some("a.txt\nb.txt\nc\n"), || copy.try_parse_from(Vec::<String>::new()));
+    assert_eq!(parsed.unwrap().args().positional(), ["a.txt", "b.txt"]);
+}
+
+#[cfg(feature = "prompt")]
+#[test]
+fn prompts_need_a_terminal() {
+    let port = app(Arg::new("port").takes_value().required(true).prompt("Port"));
+    let (parsed, shown) = typing(None, || port.try_parse_from(Vec::<String>::new()));
+    assert_eq!(parsed.unwrap_err().to_string(), "required argument 'port' not provided");
+    assert!(shown.is_empty());
+
+    assert!(problems(&app(Arg::new("all").prompt("All?"))).iter().any(|p| p.contains("flag 'all' cannot have a prompt")));
+}
+
+#[cfg(feature = "repl")]
+#[test]
+fn shell_lines() {
+    let inits = Rc::new(Cell::new(0));
+    let counted = inits.clone();
+    let tool = CliApp::new("app")
+        .shell("shell")
+        .init(move |_, _| {
+            counted.set(counted.get() + 1);
+            Ok(())
+        })
+        .subcommand(Command::new("count").arg(Arg::variadic("words")).context_handler(|ctx, args| {
+            let seen = ctx.get::<usize>().copied().unwrap_or_default() + args.variadic().len();
+            ctx.insert(seen);
+            Ok(seen as i32)
+        }))
+        .subcommand(Command::new("fail").handler(|_| -> LuhTwin<()> { Err(at!("broken").into()) }))
+        .subcommand(Command::new("remote").subcommand(Command::new("add").handler(|_| Ok(()))));
+    let mut ctx = Context::new();
+
+    assert_eq!(tool.run_line("", &mut ctx).unwrap(), Some(EXIT_SUCCESS));
+    assert_eq!(tool.run_line("count 'a b' c", &mut ctx).unwrap(), Some(2));
+    assert_eq!(tool.run_line("fail", &mut ctx).unwrap_err().to_string(), "broken");
+    // the shell goes on, with the same context
+    assert_eq!(tool.run_line("count d", &mut ctx).unwrap(), Some(3));
+    assert_eq!(inits.get(), 0);
+
+    assert_eq!(tool.run_line("shell", &mut ctx).unwrap_err().to_string(), "already in the shell");
+    assert!(tool.run_line("count 'a", &mut ctx).is_err());
+    assert_eq!(tool.run_line("help", &mut ctx).unwrap(), Some(EXIT_SUCCESS));
+    assert_eq!(tool.run_line("help remote add", &mut ctx).unwrap(), Some(EXIT_SUCCESS));
+    assert_eq!(tool.run_line("help nope", &mut ctx).unwrap_err().to_string(), "unknown command: nope");
+
+    assert_eq!(tool.run_line("exit", &mut ctx).unwrap(), None);
+    assert_eq!(tool.run_line("  quit ", &mut ctx).unwrap(), None);
+}
+
+#[test]
+fn help_text() {
+    let tool = CliApp::new("app")
+        .subcommand(Command::new("remote").about("manage remotes")
+            .arg(Arg::new("format").takes_value().value_name("FMT"))
+            .arg(Arg::positional("name", 0).help("remote name")));
+    let help = tool.root.subcommands[0].help_text("app remote");
+    assert!(help.contains("│  app remote"), "{}", help);
+    assert!(help.contains("│  manage remotes"), "{}", help);
+    assert!(help.contains("<name>") && help.contains("remote name"), "{}", help);
+    assert!(help.contains("--format <FMT>"), "{}", help);
+    assert!(tool.root.help_text("app").contains("remote               manage remotes"));
+}
+
+#[cfg(feature = "repl")]
+#[test]
+fn shell_completions() {
+    let tmp = TempDir::new("completions");
+    tmp.file("notes.txt");
+    tmp.dir("docs");
+    let dir = format!("{}/", tmp.0.display());
+
+    let tool = CliApp::new("app")
+        .shell("shell")
+        .subcommand(Command::new("remote")
+            .subcommand(Command::new("add"))
+            .subcommand(Command::new("remove")))
+        .subcommand(Command::new("open")
+            .arg(Arg::new("format").short('f').takes_value().possible_values(["json", "text"]))
+            .arg(Arg::new("out").takes_value().value_hint(ValueHint::DirPath))
+            .arg(Arg::positional("kind", 0)
+                .when("file", [Arg::positional("path", 0).value_hint(ValueHint::FilePath)])
+                .when("name", [Arg::positional("label", 0)])));
+    let complete = |line: &str| crate::repl::Completions { root: &tool.root }.candidates(line);
+
+    assert_eq!(complete(""), ["exit", "help", "open", "quit", "remote"]);
+    assert_eq!(complete("re"), ["remote"]);
+    assert_eq!(complete("remote "), ["add", "remove"]);
+    assert_eq!(complete("remote rem"), ["remove"]);
+    assert_eq!(complete("open "), ["file", "name"]);
+    assert_eq!(complete("open --"), ["--format", "--out"]);
+    assert_eq!(complete("open --format "), ["json", "text"]);
+    assert_eq!(complete("open -f j"), ["json"]);
+    assert_eq!(complete(&format!("open file {}", dir)), [format!("{}docs/", dir), format!("{}notes.txt", dir)]);
+    assert_eq!(complete(&format!("open --out {}", dir)), [format!("{}docs/", dir)]);
+    assert!(complete(&format!("open name {}", dir)).is_empty());
+}
+
+#[test]
+fn allowed_external_subcommands() {
+    let plugins = CliApp::new("app")
+        .allow_external_subcommands(true)
+        .arg(Arg::new("verbose").short('v'))
+        .subcommand(Command::new("build").handler(|_| Ok(())))
+        .handler(|args| Ok(args.external_subcommand().map_or(0, |(_, rest)| rest.len() as i32)));
+
+    let matches = plugins.try_parse_from(["-v", "lint", "--fix", "src"]).unwrap();
+    assert!(matches.args().flag("verbose"));
+    assert_eq!(matches.action(), Action::Run);
+    let (name, rest) = matches.args().external_subcommand().unwrap();
+    assert_eq!(name, "lint");
+    assert_eq!(rest, ["--fix", "src"]);
+
+    assert_eq!(plugins.run_from(["lint", "--fix", "src"]).unwrap(), 2);
+    assert!(plugins.try_parse_from(["build"]).unwrap().args().external_subcommand().is_none());
+}
+
+#[cfg(unix)]
+#[test]
+fn raw_arguments() {
+    use std::ffi::{OsStr, OsString};
+    use std::os::unix::ffi::{OsStrExt, OsStringExt};
+
+    let bytes = |b: &[u8]| OsString::from_vec(b.to_vec());
+    let tool = CliApp::new("app")
+        .arg(Arg::positional("file", 0).raw())
+        .arg(Arg::new("name").takes_value())
+        .arg(Arg::new("out").takes_value().raw())
+        .arg(Arg::variadic("rest").raw());
+
+    let args = [bytes(b"a\xFF"), bytes(b"--out=b\xFE"), "c".into(), bytes(b"d\xFD")];
+    let matches = tool.try_parse_from_os(args).unwrap();
+    let parsed = matches.args();
+    assert_eq!(parsed.get_os("file"), Some(OsStr::from_bytes(b"a\xFF")));
+    assert_eq!(parsed.get("file").map(String::as_str), Some("a\u{FFFD}"));
+    assert_eq!(parsed.get_path("out").unwrap().as_os_str().as_bytes(), b"b\xFE");
+    assert_eq!(parsed.variadic_os(), [OsStr::new("c"), OsStr::from_bytes(b"d\xFD")]);
+    assert_eq!(parsed.variadic(), ["c", "d\u{FFFD}"]);
+
+    let err = tool.try_parse_from_os([bytes(b"a"), "--name".into(), bytes(b"x\xFF")]).unwrap_err();
+    assert_eq!(err.to_string(), "the value of 'name' is not valid UTF-8: 'x\u{FFFD}'");
+    let args = [bytes(b"a"), "--name".into(), bytes(b"x\xFF"), "--name".into(), "y".into()];
+    let err = tool.try_parse_from_os(args).unwrap_err();
+    assert_eq!(err.to_string(), "the value of 'name' is not valid UTF-8: 'x\u{FFFD}'");
+
+    // private use characters in valid arguments are nothing special
+    let args = [bytes(b"a\xFF"), "--name".into(), "x\u{F0000}".into(), "--out=\u{F0001}".into()];
+    let matches = tool.try_parse_from_os(args).unwrap();
+    let parsed = matches.args();
+    assert_eq!(parsed.get("name").map(String::as_str), Some("x\u{F0000}"));
+    assert_eq!(parsed.get_os("out"), Some(OsStr::new("\u{F0001}")));
+    assert_eq!(parsed.get_os("file"), Some(OsStr::from_bytes(b"a\xFF")));
+
+    let tmp = TempDir::new("raw-responses");
+    fs::write(tmp.0.join("args"), "--name n\nmore").unwrap();
+    let responding = CliApp::new("app")
+        .response_files(true)
+        .arg(Arg::new("name").takes_value())
+        .arg(Arg::variadic("rest").raw());
+    let at = format!("@{}", tmp.0.join("args").display());
+    let matches = responding.try_parse_from_os([at.into(), bytes(b"z\xFF")]).unwrap();
+    assert_eq!(matches.args().variadic_os(), [OsStr::new("more"), OsStr::from_bytes(b"z\xFF")]);
+
+    let make = CliApp::new("make").subcommand(Command::new("build").assignments(true).handler(|_| Ok(())));
+    let err = make.try_parse_from_os(["build".into(), bytes(b"CC=x\xFF")]).unwrap_err();
+    assert_eq!(err.to_string(), "the value of assignment 'CC' is not valid UTF-8: 'x\u{FFFD}'");
+
+    let plugins = CliApp::new("app").allow_external_subcommands(true).handler(|_| Ok(()));
+    let args = ["lint".into(), bytes(b"f\xFFo"), bytes(b"--path=\xFE"), "ok".into()];
+    let matches = plugins.try_parse_from_os(args).unwrap();
+    let (name, rest) = matches.args().external_subcommand().unwrap();
+    assert_eq!(name, "lint");
+    assert_eq!(rest, [bytes(b"f\xFFo"), bytes(b"--path=\xFE"), "ok".into()]);
+    assert_eq!(
+        plugins.try_parse_from_os([bytes(b"l\xFFnt")]).unwrap_err().to_string(),
+        "unknown command: l\u{FFFD}nt"
+    );
+}
+
+/// Executables on `PATH` are only looked up here, since it changes `PATH`.
+#[cfg(unix)]
+#[test]
+fn external_subcommands_on_path() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = TempDir::new("external");
+    let script = |path: PathBuf, body: &str| {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, format!("#!/bin/sh\n{}\n", body)).unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+    };
+    let bin = dir.0.join("bin");
+    script(bin.join("app-deploy"), "exit $#");
+    script(bin.join("app-bytes"), r#"[ "$1" = "$(printf 'f\377o')" ] && exit 7"#);
+    script(bin.join("app-../escape"), "exit 9");
+    script(bin.join("app-.hidden"), "exit 9");
+
+    let old_path = std::env::var_os("PATH");
+    std::env::set_var("PATH", &bin);
+    let tool = CliApp::new("app").external_subcommands("app").subcommand(Command::new("build").handler(|_| Ok(())));
+    let deploy = tool.try_parse_from(["deploy", "a", "b"]).map(|m| m.action());
+    let code = tool.run_from(["deploy", "a", "b", "c"]);
+    let bytes = {
+        use std::os::unix::ffi::OsStringExt;
+        tool.exit_code(["bytes".into(), std::ffi::OsString::from_vec(b"f\xFFo".to_vec())])
+    };
+    let escape = error(&tool, &["../escape"]);
+    let hidden = error(&tool, &[".hidden"]);
+    let listed = crate::external::list("app");
+    match old_path {
+        Some(path) => std::env::set_var("PATH", path),
+        None => std::env::remove_var("PATH"),
+    }
+
+    assert_eq!(deploy.unwrap(), Action::External);
+    assert_eq!(code.unwrap(), 3);
+    assert_eq!(bytes, 7);
+    assert_eq!(escape, "unknown command: ../escape");
+    assert_eq!(hidden, "unknown command: .hidden");
+    assert_eq!(listed, ["bytes", "deploy"]);
+}
+
+#[test]
+fn value_delimiters() {
+    let tags = app(Arg::new("tags").short('t').takes_value().value_delimiter(','));
+    let matches = tags.try_parse_from(["--tags", "a,b,,c"]).unwrap();
+    assert_eq!(matches.args().get("tags").map(String::as_str), Some("a,b,,c"));
+    assert_eq!(matches.args().get_many("tags").unwrap(), ["a", "b", "", "c"]);
+
+    let matches = tags.try_parse_from(["-t", r"a\,b,c\\,d\x"]).unwrap();
+    assert_eq!(matches.args().get_many("tags").unwrap(), ["a,b", r"c\", r"d\x"]);
+
+    // every occurrence adds its pieces, the value is still the last one
+    let matches = tags.try_parse_from(["--tags", "a,b", "-t", "c"]).unwrap();
+    assert_eq!(matches.args().get("tags").map(String::as_str), Some("c"));
+    assert_eq!(matches.args().get_many("tags").unwrap(), ["a", "b", "c"]);
+
+    let single = app(Arg::new("name").takes_value());
+    let matches = single.try_parse_from(["--name", "a,b"]).unwrap();
+    assert_eq!(matches.args().get_many("name").unwrap(), ["a,b"]);
+    let matches = single.try_parse_from(["--name", "a", "--name", "b"]).unwrap();
+    assert_eq!(matches.args().get_many("name").unwrap(), ["a", "b"]);
+}
+
+#[test]
+fn delimited_pieces_are_checked() {
+    let targets = app(Arg::new("targets").takes_value().value_delimiter(',').possible_values(["app", "lib"]));
+    assert!(targets.try_parse_from(["--targets", "app,lib"]).is_ok());
+    assert_eq!(
+        error(&targets, &["--targets", "app,bin"]),
+        "invalid value 'bin' for 'targets'. possible values: app, lib"
+    );
+
+    let variadic = app(Arg::variadic("files").value_delimiter(':'));
+    let matches = variadic.try_parse_from(["a:b", "c"]).unwrap();
+    assert_eq!(matches.args().variadic(), ["a", "b", "c"]);
+
+    let env = app(Arg::new("paths").takes_value().value_delimiter(';').env("LUHCLI_TEST_PATHS"));
+    std::env::set_var("LUHCLI_TEST_PATHS", r"x;y\;z");
+    let matches = env.try_parse_from(Vec::<String>::new());
+    std::env::remove_var("LUHCLI_TEST_PATHS");
+    assert_eq!(matches.unwrap().args().get_many("paths").unwrap(), ["x", "y;z"]);
+
+    let bad_default = app(Arg::new("targets").takes_value().value_delimiter(',')
+        .possible_values(["app", "lib"]).default_value("app,bin"));
+    assert!(problems(&bad_default).iter().any(|p| p.contains("default value 'bin' of 'targets'")));
+    assert!(problems(&app(Arg::new("all").value_delimiter(','))).iter()
+        .any(|p| p.contains("flag 'all' cannot have a value delimiter")));
+}
+
+#[test]
+fn key_value_pairs() {
+    let define = app(Arg::new("define").short('D').key_value());
+    let matches = define.try_parse_from(["-D", "name=luh", "-D", "debug=1", "-D", "name=cli", "-D", "eq=a=b"]).unwrap();
+    assert_eq!(matches.args().get_map("define").unwrap(), [
+        ("name".to_string(), "cli".to_string()),
+        ("debug".to_string(), "1".to_string()),
+        ("eq".to_string(), "a=b".to_string()),
+    ]);
+    assert_eq!(matches.args().get("define").map(String::as_str), Some("eq=a=b"));
+
+    assert_eq!(error(&define, &["-D", "name"]), "invalid value 'name' for 'define': expected KEY=VALUE");
+    assert_eq!(error(&define, &["-D", "=1"]), "invalid value '=1' for 'define': expected KEY=VALUE");
+
+    let labels = app(Arg::new("label").key_separator(':').value_delimiter(','));
+    let matches = labels.try_parse_from(["--label", "a:1,b:2", "--label", "c:3"]).unwrap();
+    let keys: Vec<_> = matches.args().get_map("label").unwrap().iter().map(|(k, _)| k.as_str()).collect();
+    assert_eq!(keys, ["a", "b", "c"]);
+    assert_eq!(matches.args().get_many("label").unwrap(), ["a:1", "b:2", "c:3"]);
+    assert_eq!(error(&labels, &["--label", "a=1"]), "invalid value 'a=1' for 'label': expected KEY:VALUE");
+}
+
+#[test]
+fn duplicate_and_allowed_keys() {
+    let policy = |policy| app(Arg::new("define").short('D').key_value().duplicate_keys(policy));
+    let args = ["-D", "a=1", "-D", "a=2"];
+    let first = policy(DuplicateKeys::First).try_parse_from(args).unwrap();
+    assert_eq!(first.args().get_map("define").unwrap(), [("a".to_string(), "1".to_string())]);
+    let last = policy(DuplicateKeys::Last).try_parse_from(args).unwrap();
+    assert_eq!(last.args().get_map("define").unwrap(), [("a".to_string(), "2".to_string())]);
+    assert_eq!(error(&policy(DuplicateKeys::Error), &args), "key 'a' of 'define' is given more than once");
+
+    let set = app(Arg::new("set").key_value().allowed_keys(["color", "pager"]));
+    assert!(set.try_parse_from(["--set", "color=never"]).is_ok());
+    assert_eq!(error(&set, &["--set", "colour=never"]), "invalid key 'colour' for 'set'. allowed keys: color, pager");
+
+    let defaulted = app(Arg::new("set").key_value().default_value("theme=dark"));
+    let matches = defaulted.try_parse_from(Vec::<String>::new()).unwrap();
+    assert_eq!(matches.args().get_map("set").unwrap(), [("theme".to_string(), "dark".to_string())]);
+
+    assert!(problems(&app(Arg::new("set").key_value().default_value("dark"))).iter()
+        .any(|p| p.contains("default value of 'set' is invalid")));
+    assert!(problems(&app(Arg::new("set").takes_value().allowed_keys(["a"]))).iter()
+        .any(|p| p.contains("'set' has allowed keys but does not take KEY=VALUE pairs")));
+    assert!(problems(&app(Arg::new("set").key_value().possible_values(["a=1"]))).iter()
+        .any(|p| p.contains("cannot have possible values or when chains")));
+}
+
+#[test]
+fn assignments() {
+    let make = CliApp::new("make").subcommand(
+        Command::new("build")
+            .assignments(true)
+            .arg(Arg::positional("target", 0))
+            .arg(Arg::new("jobs").short('j').takes_value())
+            .handler(|_| Ok(())),
+    );
+    let matches = make.try_parse_from(["build", "CC=clang", "-j", "2", "DEBUG=1", "app", "CC=gcc", "X=a=b"]).unwrap();
+    let args = matches.args();
+    assert_eq!(args.get("target").map(String::as_str), Some("app"));
+    assert_eq!(args.positional(), ["app"]);
+    assert_eq!(args.assignments(), [
+        ("CC".to_string(), "gcc".to_string()),
+        ("DEBUG".to_string(), "1".to_string()),
+        ("X".to_string(), "a=b".to_string()),
+    ]);
+    assert_eq!(args.assignment("DEBUG"), Some("1"));
+    assert_eq!(args.assignment("LD"), None);
+
+    // not an identifier before the `=`, so a positional
+    let matches = make.try_parse_from(["build", "1X=y"]).unwrap();
+    assert_eq!(matches.args().get("target").map(String::as_str), Some("1X=y"));
+    assert!(matches.args().assignments().is_empty());
+
+    let plain = app(Arg::positional("target", 0));
+    let matches = plain.try_parse_from(["CC=clang"]).unwrap();
+    assert_eq!(matches.args().get("target").map(String::as_str), Some("CC=clang"));
+}
+
+#[test]
+fn assignment_names_and_chains() {
+    let tool = CliApp::new("tool").subcommand(
+        Command::new("run")
+            .assignments(true)
+            .assignment_names(["CC", "LD"])
+            .arg(Arg::positional("mode", 0).when("copy", [Arg::positional("from", 0)]))
+            .handler(|_| Ok(())),
+    );
+    assert_eq!(error(&tool, &["run", "AR=ar"]), "unknown assignment: AR. allowed names: CC, LD");
+
+    let matches = tool.try_parse_from(["run", "CC=clang", "copy", "LD=lld", "src"]).unwrap();
+    assert_eq!(matches.args().get("from").map(String::as_str), Some("src"));
+    assert_eq!(matches.args().assignments().len(), 2);
+
+    let nested = CliApp::new("tool").subcommand(
+        Command::new("run").assignments(true).subcommand(Command::new("fast").handler(|_| Ok(()))),
+    );
+    let matches = nested.try_parse_from(["run", "CC=clang", "fast"]).unwrap();
+    assert_eq!(matches.command_path(), ["tool", "run", "fast"]);
+    assert_eq!(matches.args().parent().unwrap().assignment("CC"), Some("clang"));
+
+    let names = CliApp::new("tool").subcommand(Command::new("run").assignment_names(["CC", "2X"]).handler(|_| Ok(())));
+    let found = problems(&names);
+    assert!(found.iter().any(|p| p.contains("assignment names are set but assignments are not enabled")), "{:?}", found);
+    assert!(found.iter().any(|p| p.contains("assignment name '2X' is not an identifier")), "{:?}", found);
+}
+
+#[test]
+fn value_names_in_messages() {
+    let args = [
+        Arg::positional("input", 0).value_name("FILE").required(false),
+        Arg::variadic("rest").value_name("ARG"),
+        Arg::new("url").short('u').takes_value().value_name("URL").value_hint(ValueHint::Url),
+        Arg::new("stdin").short('s'),
+    ];
+    assert_eq!(crate::display_name(&args, "input"), "<FILE>");
+    assert_eq!(crate::display_name(&args, "rest"), "<ARG>...");
+    assert_eq!(crate::display_name(&args, "url"), "--url");
+    assert_eq!(crate::display_name(&args, "missing"), "missing");
+    assert_eq!(args[0].placeholder(), "FILE");
+    assert_eq!(args[3].placeholder(), "stdin");
+
+    let sources = CliApp::new("cat")
+        .handler(|_| Ok(()))
+        .arg(args[0].clone())
+        .arg(args[3].clone())
+        .group(ArgGroup::new("source").args(["input", "stdin"]));
+    assert_eq!(
+        error(&sources, &["a.txt", "-s"]),
+        "<FILE> and --stdin cannot be used together (group 'source' allows at most one of <FILE>, --stdin)"
+    );
+}
+
+#[test]
+fn value_names_on_flags_are_a_definition_problem() {
+    assert_eq!(problems(&app(Arg::new("all").value_name("ALL"))), ["app: flag 'all' cannot have a value name or hint"]);
+    assert_eq!(
+        problems(&app(Arg::new("all").value_hint(ValueHint::FilePath))),
+        ["app: flag 'all' cannot have a value name or hint"]
+    );
+    assert!(app(Arg::new("out").takes_value().value_name("FILE").value_hint(ValueHint::FilePath)).validate().is_ok());
+}
+
+#[test]
+fn must_exist() {
+    let tmp = TempDir::new("must-exist");
+    let app = app(Arg::positional("input", 0).must_exist());
+
+    assert!(app.try_parse_from([tmp.file("a.txt")]).is_ok());
+    assert!(app.try_parse_from([tmp.dir("sub")]).is_ok());
+
+    let missing = tmp.missing("nope.txt");
+    let err = error(&app, &[&missing]);
+    assert!(err.contains("'input'") && err.contains(&missing), "{}", err);
+    assert!(err.contains("does not exist"), "{}", err);
+}
+
+#[test]
+fn is_file_and_is_dir() {
+    let tmp = TempDir::new("kinds");
+    let file = tmp.file("a.txt");
+    let dir = tmp.dir("sub");
+
+    let files = app(Arg::new("config").takes_value().is_file());
+    assert!(files.try_parse_from(["--config", &file]).is_ok());
+    assert!(error(&files, &["--config", &dir]).contains("is not a file"));
+    assert!(error(&files, &["--config", &tmp.missing("x")]).contains("does not exist"));
+
+    let dirs = app(Arg::new("out").takes_value().is_dir());
+    assert!(dirs.try_parse_from([format!("--out={}", dir)]).is_ok());
+    assert!(error(&dirs, &["--out", &file]).contains("is not a directory"));
+}
+
+#[test]
+fn parent_exists() {
+    let tmp = TempDir::new("parent");
+    let app = app(Arg::new("out").takes_value().parent_exists());
+
+    assert!(app.try_parse_from(["--out", &tmp.missing("new.txt")]).is_ok());
+    assert!(app.try_parse_from(["--out", "bare-name.txt"]).is_ok());
+
+    let nested = tmp.missing("no/such/dir/new.txt");
+    let err = error(&app, &["--out", &nested]);
+    assert!(err.contains("parent directory") && err.contains(&nested), "{}", err);
+}
+
+#[test]
+fn extension() {
+    let app = app(Arg::positional("config", 0).extension(["json", ".toml"]));
+
+    assert!(app.try_parse_from(["settings.json"]).is_ok());
+    assert!(app.try_parse_from(["settings.TOML"]).is_ok());
+
+    let err = error(&app, &["settings.yaml"]);
+    assert!(err.contains("settings.yaml") && err.contains("json, toml"), "{}", err);
+    assert!(error(&app, &["settings"]).contains("extensions"));
+}
+
+#[test]
+fn not_exists() {
+    let tmp = TempDir::new("not-exists");
+    let app = app(Arg::new("out").takes_value().not_exists());
+
+    assert!(app.try_parse_from(["--out", &tmp.missing("fresh.txt")]).is_ok());
+
+    let taken = tmp.file("taken.txt");
+    let err = error(&app, &["--out", &taken]);
+    assert!(err.contains("already exists") && err.contains(&taken), "{}", err);
+}
+
+#[test]
+fn every_value_is_checked() {
+    let tmp = TempDir::new("values");
+    let (a, b) = (tmp.file("a"), tmp.file("b"));
+    let missing = tmp.missing("c");
+
+    let variadic = app(Arg::variadic("files").is_file());
+    assert!(variadic.try_parse_from([&a, &b]).is_ok());
+    assert!(error(&variadic, &[&a, &missing]).contains(&missing));
+
+    let delimited = app(Arg::new("include").takes_value().value_delimiter(',').must_exist());
+    assert!(delimited.try_parse_from([format!("--include={},{}", a, b)]).is_ok());
+    assert!(error(&delimited, &["--include", &format!("{},{}", a, missing)]).contains(&missing));
+
+    let defaulted = app(Arg::new("config").takes_value().is_file().default_value(missing.clone()));
+    assert!(error(&defaulted, &[]).contains(&missing));
+}
+
+#[test]
+fn checks_follow_subcommands_and_chains() {
+    let tmp = TempDir::new("chains");
+    let missing = tmp.missing("gone");
+
+    let app = CliApp::new("app").subcommand(
+        Command::new("open").handler(|_| Ok(())).arg(
+            Arg::positional("mode", 0)
+                .when("file", [Arg::positional("path", 0).is_file()])
+                .when("name", [Arg::positional("label", 0)]),
+        ),
+    );
+
+    assert!(error(&app, &["open", "file", &missing]).contains("'path'"));
+    assert!(app.try_parse_from(["open", "name", &missing]).is_ok());
+    assert!(app.try_parse_from(["open", "file", &tmp.file("here")]).is_ok());
+}
+
+#[test]
+fn contradictory_checks_are_a_definition_problem() {
+    let app = app(Arg::new("out").takes_value().must_exist().not_exists())
+        .arg(Arg::new("verbose").short('v').is_file());
+
+    let err = app.validate().unwrap_err().to_string();
+    assert!(err.contains("must both exist and not exist"), "{}", err);
+    assert!(err.contains("'verbose' does not take a path"), "{}", err);
+}
+
+#[test]
+fn validators() {
+    let app = app(Arg::new("name").takes_value().validator(|v| {
+        if v.chars().all(char::is_alphanumeric) { Ok(()) } else { Err("must be alphanumeric".into()) }
+    }));
+
+    assert!(app.try_parse_from(["--name", "luh"]).is_ok());
+    let err = error(&app, &["--name=l-u-h"]);
+    assert_eq!(err, "invalid value 'l-u-h' for 'name': must be alphanumeric");
+}
+
+#[test]
+fn length_and_range() {
+    let server = app(Arg::positional("port", 0).range(1..=65535))
+        .arg(Arg::new("name").takes_value().length(..=4));
+
+    assert!(server.try_parse_from(["8080", "--name", "luh"]).is_ok());
+    assert!(error(&server, &["0"]).contains("must be a number between 1 and 65535"));
+    assert!(error(&server, &["http"]).contains("must be a number"));
+    assert!(error(&server, &["80", "--name", "luhcli"]).contains("must be at most 4 characters long"));
+
+    let ratio = app(Arg::new("ratio").takes_value().range(0.0..1.0));
+    assert!(ratio.try_parse_from(["--ratio", "0.5"]).is_ok());
+    assert!(error(&ratio, &["--ratio", "1"]).contains("at least 0 and less than 1"));
+}
+
+#[test]
+fn validators_see_every_value() {
+    let variadic = app(Arg::variadic("ports").range(1u16..));
+    assert!(variadic.try_parse_from(["1", "2"]).is_ok());
+    assert!(error(&variadic, &["1", "0"]).contains("'0'"));
+
+    let delimited = app(Arg::new("tags").takes_value().value_delimiter(',').length(1..));
+    assert!(delimited.try_parse_from(["--tags", "a,b"]).is_ok());
+    assert!(error(&delimited, &["--tags", "a,,b"]).contains("invalid value '' for 'tags'"));
+
+    let bad_default = app(Arg::new("port").takes_value().range(1..=10).default_value("80"));
+    assert!(bad_default.validate().unwrap_err().to_string().contains("default value '80' of 'port' is invalid"));
+}
+
+#[cfg(feature = "regex")]
+#[test]
+fn matches_regex() {
+    let app = app(Arg::new("tag").takes_value().matches(regex::Regex::new(r"^v\d+$").unwrap()));
+
+    assert!(app.try_parse_from(["--tag", "v12"]).is_ok());
+    assert!(error(&app, &["--tag", "12"]).contains(r"must match '^v\d+$'"));
+}
+
+fn actions() -> Arg {
+    Arg::positional("action", 0).possible_values([
+        PossibleValue::new("get").help("print a key"),
+        PossibleValue::new("list").alias("ls").help("print every key"),
+        PossibleValue::new("dump").hide(true),
+    ])
+}
+
+#[test]
+fn possible_value_aliases() {
+    let app = app(actions());
+
+    let matches = app.try_parse_from(["ls"]).unwrap();
+    assert_eq!(matches.args().get("action").unwrap(), "list");
+    assert_eq!(matches.args().pos(0).unwrap(), "list");
+
+    assert!(app.try_parse_from(["dump"]).is_ok());
+    assert_eq!(error(&app, &["put"]), "invalid value 'put' for 'action'. possible values: get, list");
+}
+
+#[test]
+fn possible_values_ignoring_case() {
+    let strict = app(Arg::new("format").takes_value().possible_values(["json", "toml"]));
+    assert!(strict.try_parse_from(["--format", "JSON"]).is_err());
+
+    let loose = app(Arg::new("format").takes_value().possible_values(["json", "toml"]).ignore_case(true)
+        .value_delimiter(','));
+    let matches = loose.try_parse_from(["--format", "JSON,Toml"]).unwrap();
+    assert_eq!(matches.args().get("format").unwrap(), "json,toml");
+    assert_eq!(matches.args().get_many("format").unwrap(), ["json", "toml"]);
+}
+
+#[test]
+fn when_chains_follow_aliases() {
+    let app = app(actions().when("list", [Arg::new("long").short('l')]))
+        .arg(Arg::new("out").takes_value().required_if_eq("action", "list"));
+
+    assert!(app.try_parse_from(["ls", "-l", "--out", "x"]).is_ok());
+    assert!(error(&app, &["ls", "-l"]).contains("'out' is required"));
+    assert!(error(&app, &["get", "-l"]).contains("unknown option"));
+}
+
+#[test]
+fn possible_values_in_help() {
+    assert_eq!(crate::possible_value_lines(&actions()), [
+        "possible values:",
+        "  get       print a key",
+        "  list, ls  print every key",
+    ]);
+    assert!(crate::possible_value_lines(&Arg::new("x").possible_values(["a", "b"])).is_empty());
+}
+
+#[test]
+fn clashing_possible_values_are_a_definition_problem() {
+    let app = app(Arg::new("format").takes_value().ignore_case(true)
+        .possible_values([PossibleValue::new("json").alias("j"), PossibleValue::new("J")]));
+
+    let err = app.validate().unwrap_err().to_string();
+    assert!(err.contains("possible value 'J' of 'format' is given more than once"), "{}", err);
+}
+
+#[test]
+fn option_values_are_checked() {
+    let format = app(Arg::new("format").short('f').takes_value().possible_values(["json", "csv"]));
+    let expected = "invalid value 'yaml' for 'format'. possible values: json, csv";
+
+    assert_eq!(error(&format, &["--format", "yaml"]), expected);
+    assert_eq!(error(&format, &["--format=yaml"]), expected);
+    assert_eq!(error(&format, &["-f", "yaml"]), expected);
+    assert!(format.try_parse_from(["--format=csv"]).is_ok());
+
+    // every occurrence is checked, not just the one that ends up as the value
+    assert_eq!(error(&format, &["--format=yaml", "--format", "csv"]), expected);
+
+    let targets = app(Arg::new("targets").takes_value().value_delimiter(',').possible_values(["app", "lib"]));
+    assert_eq!(
+        error(&targets, &["--targets=app,lib", "--targets", "bin"]),
+        "invalid value 'bin' for 'targets'. possible values: app, lib"
+    );
+
+    let missing = app(Arg::new("color").takes_value().possible_values(["auto", "always"]).default_missing_value("never"));
+    assert!(missing.validate().unwrap_err().to_string().contains("default value 'never' of 'color'"));
+}
+
+#[test]
+fn variadic_and_env_values_are_checked() {
+    let variadic = app(Arg::variadic("targets").possible_values(["app", "lib"]));
+    assert!(variadic.try_parse_from(["app", "lib"]).is_ok());
+    assert!(error(&variadic, &["app", "bin"]).contains("invalid value 'bin' for 'targets'"));
+
+    let env = app(Arg::new("level").takes_value().possible_values(["info", "debug"]).env("LUHCLI_TEST_LEVEL"));
+    std::env::set_var("LUHCLI_TEST_LEVEL", "loud");
+    let err = error(&env, &[]);
+    std::env::remove_var("LUHCLI_TEST_LEVEL");
+    assert!(err.contains("(from environment variable LUHCLI_TEST_LEVEL)"), "{}", err);
+}
+
+#[test]
+fn defaults_go_through_possible_values() {
+    let actions = app(actions().default_value("ls"));
+    assert_eq!(actions.try_parse_from(Vec::<String>::new()).unwrap().args().get("action").unwrap(), "list");
+
+    let bad = app(Arg::new("format").takes_value().possible_values(["json"]).default_value("yaml"));
+    assert!(bad.validate().unwrap_err().to_string().contains("default value 'yaml' of 'format'"));
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Mode {
+    Fast,
+    Careful,
+}
+
+impl ValueEnum for Mode {
+    fn value_variants() -> &'static [Self] {
+        &[Mode::Fast, Mode::Careful]
+    }
+
+    fn to_possible_value(&self) -> PossibleValue {
+        match self {
+            Mode::Fast => PossibleValue::new("fast").help("skip the checks"),
+            Mode::Careful => PossibleValue::new("careful").alias("safe").help("ask before anything"),
+        }
+    }
+}
+
+#[test]
+fn value_enums() {
+    let arg = Arg::new("mode").takes_value().value_enum::<Mode>().ignore_case(true);
+    assert_eq!(crate::possible_value_lines(&arg)[1], "  fast           skip the checks");
+
+    let modes = app(arg);
+    let mode = |args: &[&str]| modes.try_parse_from(args.iter().copied()).unwrap().args().get_enum::<Mode>("mode");
+
+    assert_eq!(mode(&["--mode", "fast"]), Some(Mode::Fast));
+    assert_eq!(mode(&["--mode", "SAFE"]), Some(Mode::Careful));
+    assert_eq!(mode(&[]), None);
+    assert!(error(&modes, &["--mode", "slow"]).contains("possible values: fast, careful"));
+}
+
+#[test]
+fn chains_keyed_by_variant() {
+    let modes = app(Arg::positional("mode", 0).value_enum::<Mode>()
+        .when_variant(Mode::Careful, [Arg::new("log").takes_value().required(true)]));
+
+    assert!(modes.try_parse_from(["fast"]).is_ok());
+    assert!(error(&modes, &["safe"]).contains("log"));
+
+    let parsed = modes.try_parse_from(["safe", "--log", "out.txt"]).unwrap();
+    assert_eq!(parsed.args().get_enum::<Mode>("mode"), Some(Mode::Careful));
+    assert_eq!(parsed.args().get("log").unwrap(), "out.txt");
+}
@@ -0,0 +1,169 @@
+//! `#[derive(LuhParser)]`, `#[derive(LuhSubcommand)]` and `#[derive(ValueEnum)]`
+//! from the outside, the way applications use them.
+
+use std::ffi::OsString;
+use std::path::PathBuf;
+use luhcli::{LuhParser, LuhSubcommand, ValueEnum};
+
+/// Manage remotes
+#[derive(Debug, LuhParser)]
+#[luhcli(name = "remote", version = "1.0")]
+struct Cli {
+    /// Print more
+    #[luhcli(short)]
+    verbose: bool,
+    #[luhcli(short = 'j', default = "4")]
+    jobs: u32,
+    #[luhcli(long = "cfg")]
+    config: Option<PathBuf>,
+    #[luhcli(subcommand)]
+    command: Action,
+}
+
+#[derive(Debug, PartialEq, LuhSubcommand)]
+enum Action {
+    /// Add a remote
+    Add {
+        #[luhcli(positional)]
+        name: String,
+        #[luhcli(positional)]
+        url: Option<String>,
+        #[luhcli(default = "fetch", possible_values("fetch", "push"))]
+        mode: String,
+        #[luhcli(value_enum)]
+        protocol: Option<Protocol>,
+        tags: Vec<String>,
+    },
+    /// List remotes
+    #[luhcli(name = "ls")]
+    ListAll,
+    Copy(Copy),
+    HTTPServer,
+    Config {
+        #[luhcli(subcommand)]
+        action: Option<ConfigAction>,
+    },
+}
+
+#[derive(Debug, PartialEq, LuhParser)]
+struct Copy {
+    #[luhcli(positional)]
+    from: PathBuf,
+    #[luhcli(positional)]
+    to: OsString,
+}
+
+#[derive(Debug, PartialEq, LuhSubcommand)]
+enum ConfigAction {
+    Get {
+        #[luhcli(positional)]
+        key: String,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+enum Protocol {
+    /// Plain HTTPS
+    Https,
+    #[luhcli(alias = "git+ssh")]
+    Ssh,
+    #[luhcli(hide)]
+    GitDaemon,
+}
+
+fn parse(args: &[&str]) -> Cli {
+    Cli::try_parse_from(args).unwrap().unwrap()
+}
+
+fn error(args: &[&str]) -> String {
+    Cli::try_parse_from(args).unwrap_err().to_string()
+}
+
+#[test]
+fn flags_options_and_defaults() {
+    let cli = parse(&["ls"]);
+    assert!(!cli.verbose);
+    assert_eq!(cli.jobs, 4);
+    assert_eq!(cli.config, None);
+    assert_eq!(cli.command, Action::ListAll);
+
+    let cli = parse(&["-v", "-j", "8", "--cfg", "app.toml", "ls"]);
+    assert!(cli.verbose);
+    assert_eq!(cli.jobs, 8);
+    assert_eq!(cli.config, Some(PathBuf::from("app.toml")));
+
+    assert_eq!(error(&["-j", "many", "ls"]), "invalid value 'many' for 'jobs': invalid digit found in string");
+    assert!(Cli::try_parse_from(["--version"]).unwrap().is_none());
+}
+
+#[test]
+fn positionals_options_and_variadics() {
+    let cli = parse(&["add", "origin", "--mode", "push", "--protocol", "git+ssh", "a", "b"]);
+    assert_eq!(cli.command, Action::Add {
+        name: "origin".into(),
+        url: Some("a".into()),
+        mode: "push".into(),
+        protocol: Some(Protocol::Ssh),
+        tags: vec!["b".into()],
+    });
+
+    let cli = parse(&["add", "origin"]);
+    assert_eq!(cli.command, Action::Add {
+        name: "origin".into(),
+        url: None,
+        mode: "fetch".into(),
+        protocol: None,
+        tags: Vec::new(),
+    });
+
+    assert_eq!(error(&["add", "origin", "--mode", "pull"]), "invalid value 'pull' for 'mode'. possible values: fetch, push");
+    assert_eq!(
+        error(&["add", "origin", "--protocol", "ftp"]),
+        "invalid value 'ftp' for 'protocol'. possible values: https, ssh"
+    );
+    assert_eq!(error(&["add"]), "missing required positional argument: name");
+}
+
+#[test]
+fn nested_and_wrapped_subcommands() {
+    assert_eq!(parse(&["config", "get", "color"]).command, Action::Config {
+        action: Some(ConfigAction::Get { key: "color".into() }),
+    });
+    assert_eq!(parse(&["config"]).command, Action::Config { action: None });
+
+    assert_eq!(parse(&["copy", "a.txt", "b.txt"]).command, Action::Copy(Copy {
+        from: "a.txt".into(),
+        to: "b.txt".into(),
+    }));
+    assert_eq!(error(&["list-all"]), "unknown command: list-all");
+    assert_eq!(parse(&["http-server"]).command, Action::HTTPServer);
+}
+
+#[cfg(unix)]
+#[test]
+fn paths_keep_their_bytes() {
+    use std::os::unix::ffi::OsStringExt;
+
+    let from = OsString::from_vec(b"caf\xE9.txt".to_vec());
+    let cli = Cli::try_parse_from([OsString::from("copy"), from.clone(), from.clone()]).unwrap().unwrap();
+    assert_eq!(cli.command, Action::Copy(Copy { from: from.clone().into(), to: from.clone() }));
+
+    let err = Cli::try_parse_from([OsString::from("add"), from]).unwrap_err().to_string();
+    assert_eq!(err, "the value of 'name' is not valid UTF-8: 'caf\u{FFFD}.txt'");
+}
+
+#[test]
+fn value_enums() {
+    assert_eq!(Protocol::value_variants(), [Protocol::Https, Protocol::Ssh, Protocol::GitDaemon]);
+    assert_eq!(Protocol::from_value("git-daemon"), Some(Protocol::GitDaemon));
+    assert_eq!(Protocol::from_value("git+ssh"), Some(Protocol::Ssh));
+
+    let https = Protocol::Https.to_possible_value();
+    assert_eq!((https.name.as_str(), https.help.as_str()), ("https", "Plain HTTPS"));
+    assert!(Protocol::GitDaemon.to_possible_value().hidden);
+}
+
+#[test]
+fn definitions_are_valid() {
+    Cli::app().validate().unwrap();
+}